@@ -0,0 +1,216 @@
+//! Special attacks and spells monsters use on top of their behaviour, with
+//! some turns to wait between two uses. The AI decides when to use them.
+use crate::constants::{ABILITY_COST, HEAL_ALLY_BELOW};
+use crate::damage::DamageType;
+use crate::effects;
+use crate::element::{Element, capitalize};
+use crate::map::free_spot_near;
+use crate::messages::MessageLog;
+use crate::state::GameState;
+use crate::status::{self, StatusKind};
+use tcod::colors;
+
+/// cosine of the angle between the middle and the edges of breath cones
+const BREATH_CONE_COS: f32 = 0.85;
+
+#[derive(Clone, Copy, Debug)]
+pub enum Ability {
+    /// hits the first element between the user and the target
+    Bolt { name: &'static str, damage: i32, damage_type: DamageType, range: i32 },
+    /// heals the most wounded ally in sight, the user included
+    HealAlly { amount: i32, range: i32 },
+    /// calls monsters made by the given template around the user
+    Summon { make: fn(i32, i32) -> Element, count: i32 },
+    /// teleports next to the target, or as far from it as possible once
+    /// it is already close
+    Blink { range: i32 },
+    /// puts a status on the target
+    Hex { status: StatusKind, turns: i32, range: i32 },
+    /// hits every element in a cone towards the target
+    Breath { name: &'static str, damage: i32, damage_type: DamageType, range: i32 },
+}
+
+/// An ability a monster has, and when it can use it again
+#[derive(Clone, Copy, Debug)]
+pub struct KnownAbility {
+    pub ability: Ability,
+    /// turns to wait between two uses
+    pub cooldown: i32,
+    /// turns left before the next use
+    pub ready_in: i32,
+}
+
+impl KnownAbility {
+    pub fn new(ability: Ability, cooldown: i32) -> Self {
+        KnownAbility { ability, cooldown, ready_in: 0 }
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.ready_in <= 0
+    }
+}
+
+/// Count a turn down on the cooldowns of the element's abilities.
+pub fn cool_down(element: &mut Element) {
+    for known in element.abilities.iter_mut() {
+        if known.ready_in > 0 {
+            known.ready_in -= 1;
+        }
+    }
+}
+
+/// Use the element's ability of the given index against the target,
+/// spending the element's time.
+pub fn use_ability(user_id: usize, index: usize, target_id: usize, game_state: &mut GameState) {
+    let ability = game_state.elements[user_id].abilities[index].ability;
+    let ally_id = match ability {
+        Ability::HealAlly { range, .. } => heal_target(user_id, range, game_state),
+        _ => None,
+    };
+    let destination = match ability {
+        Ability::Blink { range } => blink_destination(user_id, target_id, range, game_state),
+        _ => None,
+    };
+    let mut unseen_log = vec![];
+    let log = if seen(&[user_id, target_id], game_state) {
+        &mut game_state.log
+    } else {
+        &mut unseen_log
+    };
+    let elements = &mut game_state.elements;
+    let user = capitalize(&elements[user_id].name_in_messages());
+    match ability {
+        Ability::Bolt { name, damage, damage_type, .. } => {
+            log.add(format!("{} casts a {}!", user, name), colors::LIGHT_BLUE);
+            let from = elements[user_id].pos();
+            let to = elements[target_id].pos();
+            if let Some(hit_id) = game_state.map.first_blocking_element(from, to, elements) {
+                if let Some(xp) = effects::damage(&mut elements[hit_id], name, damage, damage_type, log) {
+                    elements[user_id].gain_xp(xp, log);
+                }
+            }
+        }
+        Ability::HealAlly { amount, .. } => {
+            log.add(format!("{} chants a healing spell.", user), colors::LIGHT_VIOLET);
+            effects::heal(&mut elements[ally_id.unwrap_or(user_id)], amount, log);
+        }
+        Ability::Summon { make, count } => {
+            log.add(format!("{} calls for help!", user), colors::ORANGE);
+            let position = elements[user_id].pos();
+            for _ in 0..count {
+                if let Some((x, y)) = free_spot_near(&game_state.map, elements, position, 2) {
+                    let mut summoned = make(x, y);
+                    summoned.pack = elements[user_id].pack.map(|mut member| {
+                        member.leader = false;
+                        member
+                    });
+                    summoned.last_seen = elements[user_id].last_seen;
+                    elements.push(summoned);
+                }
+            }
+        }
+        Ability::Blink { .. } => {
+            if let Some((x, y)) = destination {
+                log.add(format!("{} blinks!", user), colors::LIGHT_BLUE);
+                elements[user_id].set_pos(x, y);
+            }
+        }
+        Ability::Hex { status, turns, .. } => {
+            log.add(format!("{} casts a hex!", user), colors::LIGHT_BLUE);
+            status::afflict(&mut elements[target_id], status, turns, 0, log);
+        }
+        Ability::Breath { name, damage, damage_type, range } => {
+            log.add(format!("{} breathes {}!", user, name), colors::FLAME);
+            let from = elements[user_id].pos();
+            let towards = elements[target_id].pos();
+            let map = &game_state.map;
+            let hit: Vec<usize> = (0..elements.len())
+                .filter(|&id| {
+                    let position = elements[id].pos();
+                    id != user_id &&
+                        elements[id].alive &&
+                        elements[id].fighter.is_some() &&
+                        in_cone(from, towards, position, range) &&
+                        map.has_line_of_sight(from, position)
+                })
+                .collect();
+            for id in hit {
+                if let Some(xp) = effects::damage(&mut elements[id], name, damage, damage_type, log) {
+                    elements[user_id].gain_xp(xp, log);
+                }
+            }
+        }
+    }
+    let user = &mut game_state.elements[user_id];
+    user.abilities[index].ready_in = user.abilities[index].cooldown;
+    user.energy -= ABILITY_COST;
+}
+
+/// The most wounded ally of the user it could heal, itself included, if
+/// any is below `HEAL_ALLY_BELOW` of its hit points.
+pub fn heal_target(user_id: usize, range: i32, game_state: &GameState) -> Option<usize> {
+    let elements = &game_state.elements;
+    let user = &elements[user_id];
+    elements.iter()
+        .enumerate()
+        .filter(|&(id, ally)| {
+            ally.alive &&
+                ally.faction == user.faction &&
+                (id == user_id || user.distance_to(ally) <= range as f32 &&
+                    game_state.map.has_line_of_sight(user.pos(), ally.pos()))
+        })
+        .filter_map(|(id, ally)| ally.fighter.map(|f| (id, f.hp as f32 / f.max_hp as f32)))
+        .filter(|&(_, health)| health < HEAL_ALLY_BELOW)
+        .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())
+        .map(|(id, _)| id)
+}
+
+/// Where blinking takes the user: next to a target far away, or as far as
+/// possible from a target next to it, only to tiles it can see.
+fn blink_destination(user_id: usize, target_id: usize, range: i32, game_state: &GameState) -> Option<(i32, i32)> {
+    let elements = &game_state.elements;
+    let map = &game_state.map;
+    let (x, y) = elements[user_id].pos();
+    let (target_x, target_y) = elements[target_id].pos();
+    let away = elements[user_id].distance_to(&elements[target_id]) < 2.0;
+    let mut spots = vec![];
+    for dx in -range..(range + 1) {
+        for dy in -range..(range + 1) {
+            let (spot_x, spot_y) = (x + dx, y + dy);
+            // closed doors and harmful terrain are no place to land on
+            let free = map.is_in_bounds(spot_x, spot_y) &&
+                !map[spot_x as usize][spot_y as usize].block_movement() &&
+                map[spot_x as usize][spot_y as usize].terrain.info().damage.is_none() &&
+                !elements.iter().any(|element| element.block_movement && element.pos() == (spot_x, spot_y));
+            if free && dx * dx + dy * dy <= range * range && map.has_line_of_sight((x, y), (spot_x, spot_y)) {
+                let distance = (spot_x - target_x).pow(2) + (spot_y - target_y).pow(2);
+                spots.push(((spot_x, spot_y), if away { -distance } else { distance }));
+            }
+        }
+    }
+    spots.into_iter()
+        .min_by_key(|&(_, distance)| distance)
+        .map(|(spot, _)| spot)
+}
+
+/// whether the cell is in the cone of the given range going from `from`
+/// towards `towards`
+pub fn in_cone(from: (i32, i32), towards: (i32, i32), cell: (i32, i32), range: i32) -> bool {
+    let direction = ((towards.0 - from.0) as f32, (towards.1 - from.1) as f32);
+    let offset = ((cell.0 - from.0) as f32, (cell.1 - from.1) as f32);
+    let length = (offset.0.powi(2) + offset.1.powi(2)).sqrt();
+    let direction_length = (direction.0.powi(2) + direction.1.powi(2)).sqrt();
+    if length == 0.0 || direction_length == 0.0 || length > range as f32 {
+        return false;
+    }
+    let cos = (direction.0 * offset.0 + direction.1 * offset.1) / (length * direction_length);
+    cos >= BREATH_CONE_COS
+}
+
+/// whether the player can see any of the given elements
+fn seen(ids: &[usize], game_state: &GameState) -> bool {
+    ids.iter().any(|&id| {
+        let (x, y) = game_state.elements[id].pos();
+        game_state.map.is_in_sight(x, y)
+    })
+}
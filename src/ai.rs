@@ -1,36 +1,1018 @@
-use crate::element::Element;
-use crate::fov::FovMap;
+use crate::ability::{self, Ability, heal_target, use_ability};
+use crate::constants::{
+    ATTACK_COST,
+    BLINK_AWAY_BELOW,
+    DARKNESS_SIGHT_DIVISOR,
+    FOLLOW_DISTANCE,
+    FOLLOWER_LEASH,
+    MONSTER_DIAGONAL_MOVES,
+    PLAYER,
+    RANGED_MIN_DISTANCE,
+    ROUT_TURNS,
+    SEARCH_TURNS,
+    STEALTH_FACTOR,
+    TURN_ENERGY,
+    WAKE_UP_CHANCE,
+};
+use crate::damage::DamageType;
+use crate::effects;
+use crate::element::{Ai, Element, LastSeen, PackMember};
 use crate::map::{
     Map,
-    move_by,
+    walk_by,
 };
-use crate::utils::mut_two;
+use crate::messages::MessageLog;
+use crate::pathfinding::{DIAGONAL_MOVES, ORTHOGONAL_MOVES, find_path};
+use crate::state::GameState;
+use crate::status::{self, StatusKind};
+use crate::utils::{GameRng, mut_two};
+use rand::Rng;
+use tcod::colors::{self, Color};
+
+/// Take a step along the cheapest path to the target, or straight towards
+/// it when no path exists.
+/// Returns false if the element could not move.
+fn move_towards(id: usize,
+                target_x: i32,
+                target_y: i32,
+                map: &mut Map,
+                elements: &mut [Element]) -> bool {
+    let start = elements[id].pos();
+    let path = find_path(id, (target_x, target_y), map, elements, MONSTER_DIAGONAL_MOVES);
+    let (x, y) = elements[id].pos();
+    if let Some(&(next_x, next_y)) = path.as_ref().and_then(|path| path.first()) {
+        walk_by(id, map, elements, next_x - x, next_y - y);
+        return elements[id].pos() != start;
+    }
 
-fn move_towards(id: usize, target_x: i32, target_y: i32, map: &Map, elements: &mut [Element]) {
-    let dx = target_x - elements[id].position.x;
-    let dy = target_y - elements[id].position.y;
+    let dx = target_x - x;
+    let dy = target_y - y;
     let distance = ((dx.pow(2) + dy.pow(2)) as f32).sqrt();
 
     // normalize it to length 1 (preserving direction), then round it and
     // convert to integer so the movement is restricted to the map grid
     let dx = (dx as f32 / distance).round() as i32;
     let dy = (dy as f32 / distance).round() as i32;
-    move_by(id, map, elements, dx, dy);
-}
-
-
-pub fn ai_take_turn(monster_id: usize, map: &Map, elements: &mut [Element], player_id: usize, fov_map: &FovMap) {
-    // a basic monster takes its turn. If you can see it, it can see you
-    let (monster_x, monster_y) = elements[monster_id].pos();
-    if fov_map.is_in_sight(monster_x, monster_y) {
-        if elements[monster_id].distance_to(&elements[player_id]) >= 2.0 {
-            // move towards player if far away
-            let (player_x, player_y) = elements[player_id].pos();
-            move_towards(monster_id, player_x, player_y, map, elements);
-        } else if elements[player_id].fighter.map_or(false, |f| f.hp > 0) {
-            // close enough, attack! (if the player is still alive.)
-            let (monster, player) = mut_two(monster_id, player_id, elements);
-            monster.attack(player);
+    walk_by(id, map, elements, dx, dy);
+    elements[id].pos() != start
+}
+
+/// Whether the observer notices the target this turn, through its own sight.
+/// Targets in the dark can only be noticed from closer, and stealthy ones
+/// are harder to notice the further away they are, unless the observer is
+/// already alert to them.
+pub fn notices(observer: &Element,
+               target: &Element,
+               map: &Map,
+               rng: &mut GameRng,
+               alert: bool) -> bool {
+    if status::has(observer, StatusKind::Blinded) {
+        return false;
+    }
+    let (target_x, target_y) = target.pos();
+    let radius = if map.is_lit(target_x, target_y) {
+        observer.sight_radius
+    } else {
+        observer.sight_radius / DARKNESS_SIGHT_DIVISOR
+    };
+    let distance = observer.distance_to(target);
+    if radius <= 0 || distance >= radius as f32 {
+        return false;
+    }
+    if status::has(target, StatusKind::Invisible) && distance >= 2.0 {
+        return false;
+    }
+
+    if !map.has_line_of_sight(observer.pos(), target.pos()) {
+        return false;
+    }
+    if alert {
+        return true;
+    }
+    let chance = 1.0 - target.stealth as f32 * STEALTH_FACTOR * distance / radius as f32;
+    rng.gen::<f32>() < chance
+}
+
+pub fn ai_take_turn(monster_id: usize, game_state: &mut GameState) {
+    let ai = match game_state.elements[monster_id].ai.take() {
+        Some(ai) => ai,
+        None => return,
+    };
+    let ai = if lost_its_leader(monster_id, game_state) {
+        rout(monster_id, game_state, ai)
+    } else {
+        ai
+    };
+    ability::cool_down(&mut game_state.elements[monster_id]);
+    let energy = game_state.elements[monster_id].energy;
+    let monster = &game_state.elements[monster_id];
+    let next_ai = if status::has(monster, StatusKind::Stunned) {
+        ai
+    } else if status::has(monster, StatusKind::Confused) {
+        random_step(monster_id, game_state);
+        ai
+    } else {
+        // one look around per turn, whatever the monster then does
+        let alert = game_state.elements[monster_id].last_seen.is_some();
+        let target = find_target(monster_id, game_state);
+        // sleepers only tell their pack once awake, see `sleeping_turn`
+        if target.is_some() && ai != Ai::Sleeping {
+            alert_pack(monster_id, game_state, !alert);
+        }
+        if ai != Ai::Sleeping && ability_turn(monster_id, target, game_state) {
+            ai
+        } else { match ai {
+            Ai::Basic => basic_turn(monster_id, target, game_state),
+            Ai::Wandering => wandering_turn(monster_id, target, game_state),
+            Ai::Sleeping => sleeping_turn(monster_id, target, game_state),
+            Ai::Cowardly { flee_below } => cowardly_turn(monster_id, target, game_state, flee_below),
+            Ai::Ranged { range, projectile } => {
+                ranged_turn(monster_id, target, game_state, range, projectile)
+            }
+            Ai::Follower => follower_turn(monster_id, target, game_state),
+            Ai::Routed { previous, turns } => routed_turn(monster_id, target, game_state, previous, turns),
+        }}
+    };
+    // monsters dying during their own turn lose their AI for good
+    let monster = &mut game_state.elements[monster_id];
+    if monster.alive {
+        monster.ai = Some(next_ai);
+    }
+    // doing nothing takes time too
+    if monster.energy == energy {
+        monster.energy -= TURN_ENERGY;
+    }
+}
+
+/// Use the first of the monster's ready abilities suiting how far its
+/// target is and how hurt it and its allies are.
+/// Returns false if it used none.
+fn ability_turn(monster_id: usize, target: Option<usize>, game_state: &mut GameState) -> bool {
+    if !game_state.elements[monster_id].abilities.iter().any(|known| known.is_ready()) {
+        return false;
+    }
+    let target_id = match target {
+        Some(target_id) => target_id,
+        None => return false,
+    };
+    let abilities = game_state.elements[monster_id].abilities.clone();
+    let chosen = abilities.iter().position(|known| {
+        known.is_ready() && ability_suits(monster_id, target_id, known.ability, game_state)
+    });
+    match chosen {
+        Some(index) => {
+            use_ability(monster_id, index, target_id, game_state);
+            true
+        }
+        None => false,
+    }
+}
+
+fn ability_suits(monster_id: usize, target_id: usize, ability: Ability, game_state: &GameState) -> bool {
+    let elements = &game_state.elements;
+    let (monster, target) = (&elements[monster_id], &elements[target_id]);
+    let distance = monster.distance_to(target);
+    let health = monster.fighter.map_or(1.0, |f| f.hp as f32 / f.max_hp as f32);
+    match ability {
+        Ability::Bolt { range, .. } => {
+            distance >= 2.0 &&
+                distance <= range as f32 &&
+                game_state.map.first_blocking_element(monster.pos(), target.pos(), elements) == Some(target_id)
+        }
+        Ability::HealAlly { range, .. } => heal_target(monster_id, range, game_state).is_some(),
+        Ability::Summon { .. } => true,
+        // away from a close target when hurt, or closer to a far one
+        Ability::Blink { range } => if distance < 2.0 {
+            health < BLINK_AWAY_BELOW
+        } else {
+            health >= BLINK_AWAY_BELOW && distance > range as f32 / 2.0
+        },
+        Ability::Hex { status, range, .. } => {
+            distance <= range as f32 &&
+                !status::has(target, status) &&
+                game_state.map.has_line_of_sight(monster.pos(), target.pos())
+        }
+        Ability::Breath { range, .. } => {
+            distance <= range as f32 && game_state.map.has_line_of_sight(monster.pos(), target.pos())
+        }
+    }
+}
+
+/// The nearest hostile element the monster notices, remembering where it
+/// saw it. Monsters already after an enemy keep seeing enemies in sight.
+fn find_target(monster_id: usize, game_state: &mut GameState) -> Option<usize> {
+    let elements = &game_state.elements;
+    let monster = &elements[monster_id];
+    let alert = monster.last_seen.is_some();
+    let mut target: Option<(usize, f32)> = None;
+    for (id, element) in elements.iter().enumerate() {
+        let is_enemy = id != monster_id &&
+            element.alive &&
+            element.fighter.is_some() &&
+            monster.faction.is_hostile_to(element.faction);
+        if !is_enemy {
+            continue;
+        }
+        let distance = monster.distance_to(element);
+        let is_nearer = target.is_none_or(|(_, nearest)| distance < nearest);
+        if is_nearer && notices(monster, element, &game_state.map, &mut game_state.rng, alert) {
+            target = Some((id, distance));
+        }
+    }
+
+    let (target_id, _) = target?;
+    let (x, y) = elements[target_id].pos();
+    let last_seen = LastSeen {
+        x,
+        y,
+        searching: false,
+        search_turns: SEARCH_TURNS,
+    };
+    game_state.elements[monster_id].last_seen = Some(last_seen);
+    Some(target_id)
+}
+
+/// The other living members of the monster's pack.
+fn pack_mates(monster_id: usize, game_state: &GameState) -> Vec<usize> {
+    let pack = match game_state.elements[monster_id].pack {
+        Some(member) => member.pack,
+        None => return vec![],
+    };
+    game_state.elements.iter()
+        .enumerate()
+        .filter(|&(id, element)| {
+            id != monster_id && element.alive && element.pack.map(|member| member.pack) == Some(pack)
+        })
+        .map(|(id, _)| id)
+        .collect()
+}
+
+/// Send the rest of the pack after the monster's target, waking up those
+/// asleep. Monsters which just spotted it call out to the others.
+fn alert_pack(monster_id: usize, game_state: &mut GameState, call_out: bool) {
+    let mates = pack_mates(monster_id, game_state);
+    let last_seen = match game_state.elements[monster_id].last_seen {
+        Some(last_seen) if !mates.is_empty() => last_seen,
+        _ => return,
+    };
+    if call_out {
+        let message = format!("The {} calls out to its pack!", game_state.elements[monster_id].display_name);
+        message_if_seen(monster_id, game_state, message, colors::ORANGE);
+    }
+    for id in mates {
+        game_state.elements[id].last_seen = Some(last_seen);
+        if game_state.elements[id].ai == Some(Ai::Sleeping) {
+            game_state.elements[id].ai = Some(Ai::Basic);
+            let message = format!("The {} wakes up!", game_state.elements[id].display_name);
+            message_if_seen(id, game_state, message, colors::ORANGE);
+        }
+    }
+}
+
+/// whether the monster is in a pack whose leader is dead
+fn lost_its_leader(monster_id: usize, game_state: &GameState) -> bool {
+    let pack = match game_state.elements[monster_id].pack {
+        Some(member) if !member.leader => member.pack,
+        _ => return false,
+    };
+    let leader = Some(PackMember { pack, leader: true });
+    !game_state.elements.iter().any(|element| element.alive && element.pack == leader)
+}
+
+/// Make a monster which lost its pack leader run away for a while, on its own.
+fn rout(monster_id: usize, game_state: &mut GameState, ai: Ai) -> Ai {
+    game_state.elements[monster_id].pack = None;
+    let message = format!("The {} flees in panic!", game_state.elements[monster_id].display_name);
+    message_if_seen(monster_id, game_state, message, colors::ORANGE);
+    Ai::Routed { previous: Box::new(ai), turns: ROUT_TURNS }
+}
+
+/// A free tile next to the target, the nearest to the monster, for pack
+/// members to surround the target instead of queuing behind each other.
+fn free_spot_around(monster_id: usize, target_id: usize, game_state: &GameState) -> Option<(i32, i32)> {
+    let elements = &game_state.elements;
+    let map = &game_state.map;
+    let (x, y) = elements[monster_id].pos();
+    let (target_x, target_y) = elements[target_id].pos();
+    ORTHOGONAL_MOVES.iter()
+        .chain(DIAGONAL_MOVES.iter().filter(|_| MONSTER_DIAGONAL_MOVES))
+        .map(|&(dx, dy)| (target_x + dx, target_y + dy))
+        .filter(|&(spot_x, spot_y)| {
+            map.is_in_bounds(spot_x, spot_y) &&
+                map[spot_x as usize][spot_y as usize].is_passable() &&
+                !elements.iter().enumerate().any(|(id, element)| {
+                    id != monster_id && element.block_movement && element.pos() == (spot_x, spot_y)
+                })
+        })
+        .min_by_key(|&(spot_x, spot_y)| (spot_x - x).pow(2) + (spot_y - y).pow(2))
+}
+
+/// Go to where an enemy was last seen, then search around there for a
+/// while before giving up.
+/// Returns false if the monster is not after anyone, and did nothing.
+fn track_target(monster_id: usize, game_state: &mut GameState) -> bool {
+    let mut last_seen = match game_state.elements[monster_id].last_seen {
+        Some(last_seen) => last_seen,
+        None => return false,
+    };
+    if !last_seen.searching && game_state.elements[monster_id].pos() != (last_seen.x, last_seen.y) {
+        // once stuck on the way, search from there
+        last_seen.searching = !move_towards(monster_id,
+                                            last_seen.x,
+                                            last_seen.y,
+                                            &mut game_state.map,
+                                            &mut game_state.elements);
+        game_state.elements[monster_id].last_seen = Some(last_seen);
+        return true;
+    }
+    if last_seen.search_turns <= 0 {
+        game_state.elements[monster_id].last_seen = None;
+        return false;
+    }
+    random_step(monster_id, game_state);
+    last_seen.searching = true;
+    last_seen.search_turns -= 1;
+    game_state.elements[monster_id].last_seen = Some(last_seen);
+    true
+}
+
+/// log a message about a monster, if the player can see it
+fn message_if_seen(monster_id: usize, game_state: &mut GameState, message: String, color: Color) {
+    let (x, y) = game_state.elements[monster_id].pos();
+    if game_state.map.is_in_sight(x, y) {
+        game_state.log.add(message, color);
+    }
+}
+
+/// walk to the target, and attack once next to it
+fn approach_and_attack(monster_id: usize, target_id: usize, game_state: &mut GameState) {
+    if game_state.elements[monster_id].distance_to(&game_state.elements[target_id]) >= 2.0 {
+        // move towards the target if far away, packs spreading around it
+        let spot = if game_state.elements[monster_id].pack.is_some() {
+            free_spot_around(monster_id, target_id, game_state)
+        } else {
+            None
+        };
+        let (target_x, target_y) = spot.unwrap_or(game_state.elements[target_id].pos());
+        move_towards(monster_id, target_x, target_y, &mut game_state.map, &mut game_state.elements);
+    } else {
+        attack_target(monster_id, target_id, game_state);
+    }
+}
+
+/// Attack the target, telling the player about it only if they can see
+/// either side.
+fn attack_target(monster_id: usize, target_id: usize, game_state: &mut GameState) {
+    let seen = [monster_id, target_id].iter().any(|&id| {
+        let (x, y) = game_state.elements[id].pos();
+        game_state.map.is_in_sight(x, y)
+    });
+    let mut unseen_log = vec![];
+    let log = if seen { &mut game_state.log } else { &mut unseen_log };
+
+    // (if the target is still alive.)
+    let elements = &mut game_state.elements;
+    if elements[target_id].fighter.is_some_and(|f| f.hp > 0) {
+        let (monster, target) = mut_two(monster_id, target_id, elements);
+        monster.attack(target, &mut game_state.rng, log);
+    }
+    elements[monster_id].energy -= ATTACK_COST;
+}
+
+/// Shoot the projectile, such as an arrow, at the target, telling the
+/// player about it only if they can see either side.
+fn shoot_target(monster_id: usize, target_id: usize, projectile: &str, game_state: &mut GameState) {
+    let seen = [monster_id, target_id].iter().any(|&id| {
+        let (x, y) = game_state.elements[id].pos();
+        game_state.map.is_in_sight(x, y)
+    });
+    let mut unseen_log = vec![];
+    let log = if seen { &mut game_state.log } else { &mut unseen_log };
+
+    let elements = &mut game_state.elements;
+    if elements[target_id].fighter.is_some_and(|f| f.hp > 0) {
+        let (monster, target) = mut_two(monster_id, target_id, elements);
+        match monster.roll_ranged_damage(target, &mut game_state.rng) {
+            Some(damage) => {
+                let damage_type = monster.fighter.map_or(DamageType::Physical, |f| f.damage_type);
+                if let Some(xp) = effects::damage(target, projectile, damage, damage_type, log) {
+                    monster.gain_xp(xp, log);
+                }
+            }
+            None => {
+                let color = if target.is_player() { colors::ORANGE } else { colors::WHITE };
+                log.add(format!("The {} misses {}.", projectile, target.name_in_messages()), color);
+            }
+        }
+    }
+    elements[monster_id].energy -= ATTACK_COST;
+}
+
+/// Step away from the target, along the flee map when running from the
+/// player, or to the farthest tile around otherwise.
+/// Returns false if there is nowhere to go.
+fn flee(monster_id: usize, target_id: usize, game_state: &mut GameState) -> bool {
+    let (x, y) = game_state.elements[monster_id].pos();
+    let step = if target_id == PLAYER {
+        game_state.flee_map.next_step(monster_id, &game_state.map, &game_state.elements)
+    } else {
+        let (target_x, target_y) = game_state.elements[target_id].pos();
+        let distance = |(x, y): (i32, i32)| (x - target_x).pow(2) + (y - target_y).pow(2);
+        let mut steps = vec![];
+        for dx in -1..2 {
+            for dy in -1..2 {
+                let position = (x + dx, y + dy);
+                if distance(position) > distance((x, y)) &&
+                    game_state.map[position.0 as usize][position.1 as usize].is_passable() {
+                    steps.push((position, distance(position)));
+                }
+            }
+        }
+        steps.into_iter()
+            .max_by_key(|&(_, distance)| distance)
+            .map(|(position, _)| position)
+    };
+    let moved = step.map(|(step_x, step_y)| {
+        walk_by(monster_id, &mut game_state.map, &mut game_state.elements, step_x - x, step_y - y);
+        game_state.elements[monster_id].pos() != (x, y)
+    });
+    moved == Some(true)
+}
+
+fn random_step(monster_id: usize, game_state: &mut GameState) {
+    let dx = game_state.rng.gen_range(-1, 2);
+    let dy = game_state.rng.gen_range(-1, 2);
+    walk_by(monster_id, &mut game_state.map, &mut game_state.elements, dx, dy);
+}
+
+fn basic_turn(monster_id: usize, target: Option<usize>, game_state: &mut GameState) -> Ai {
+    match target {
+        Some(target_id) => approach_and_attack(monster_id, target_id, game_state),
+        None => {
+            track_target(monster_id, game_state);
+        }
+    }
+    Ai::Basic
+}
+
+fn wandering_turn(monster_id: usize, target: Option<usize>, game_state: &mut GameState) -> Ai {
+    match target {
+        Some(target_id) => approach_and_attack(monster_id, target_id, game_state),
+        None => if !track_target(monster_id, game_state) {
+            random_step(monster_id, game_state);
+        },
+    }
+    Ai::Wandering
+}
+
+fn sleeping_turn(monster_id: usize, target: Option<usize>, game_state: &mut GameState) -> Ai {
+    if target.is_some() && game_state.rng.gen::<f32>() < WAKE_UP_CHANCE {
+        let message = format!("The {} wakes up!", game_state.elements[monster_id].display_name);
+        message_if_seen(monster_id, game_state, message, colors::ORANGE);
+        alert_pack(monster_id, game_state, true);
+        return Ai::Basic;
+    }
+    Ai::Sleeping
+}
+
+fn cowardly_turn(monster_id: usize,
+                 target: Option<usize>,
+                 game_state: &mut GameState,
+                 flee_below: f32) -> Ai {
+    let wounded = game_state.elements[monster_id].fighter.is_some_and(|f| {
+        (f.hp as f32) < f.max_hp as f32 * flee_below
+    });
+    match target {
+        Some(target_id) => {
+            // cornered monsters fight back
+            if !wounded || !flee(monster_id, target_id, game_state) {
+                approach_and_attack(monster_id, target_id, game_state);
+            }
+        }
+        None => if !wounded {
+            track_target(monster_id, game_state);
+        },
+    }
+    Ai::Cowardly { flee_below }
+}
+
+fn ranged_turn(monster_id: usize,
+               target: Option<usize>,
+               game_state: &mut GameState,
+               range: i32,
+               projectile: &'static str) -> Ai {
+    let target_id = match target {
+        Some(target_id) => target_id,
+        None => {
+            track_target(monster_id, game_state);
+            return Ai::Ranged { range, projectile };
+        }
+    };
+    let elements = &game_state.elements;
+    let distance = elements[monster_id].distance_to(&elements[target_id]);
+    let in_line_of_fire = game_state.map.first_blocking_element(
+        elements[monster_id].pos(), elements[target_id].pos(), elements) == Some(target_id);
+    let backed_away = distance < RANGED_MIN_DISTANCE && flee(monster_id, target_id, game_state);
+    if backed_away {
+        return Ai::Ranged { range, projectile };
+    }
+    // cornered ones fight in melee
+    if distance >= 2.0 && distance <= range as f32 && in_line_of_fire {
+        shoot_target(monster_id, target_id, projectile, game_state);
+    } else {
+        approach_and_attack(monster_id, target_id, game_state);
+    }
+    Ai::Ranged { range, projectile }
+}
+
+fn follower_turn(ally_id: usize, target: Option<usize>, game_state: &mut GameState) -> Ai {
+    let target_id = target.filter(|&target_id| {
+        let elements = &game_state.elements;
+        elements[target_id].distance_to(&elements[PLAYER]) <= FOLLOWER_LEASH
+    });
+    if let Some(target_id) = target_id {
+        approach_and_attack(ally_id, target_id, game_state);
+        return Ai::Follower;
+    }
+
+    let elements = &game_state.elements;
+    if elements[ally_id].distance_to(&elements[PLAYER]) > FOLLOW_DISTANCE {
+        let step = game_state.player_map.next_step(ally_id, &game_state.map, elements);
+        if let Some((step_x, step_y)) = step {
+            let (x, y) = elements[ally_id].pos();
+            walk_by(ally_id, &mut game_state.map, &mut game_state.elements, step_x - x, step_y - y);
+        }
+    }
+    Ai::Follower
+}
+
+fn routed_turn(monster_id: usize,
+               target: Option<usize>,
+               game_state: &mut GameState,
+               previous: Box<Ai>,
+               turns: i32) -> Ai {
+    if let Some(target_id) = target {
+        // cornered monsters fight back
+        if !flee(monster_id, target_id, game_state) {
+            approach_and_attack(monster_id, target_id, game_state);
+        }
+    }
+    if turns > 1 {
+        return Ai::Routed { previous, turns: turns - 1 };
+    }
+    *previous
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::element::{
+        make_dog,
+        make_drake,
+        make_goblin,
+        make_orc,
+        make_orc_archer,
+        make_orc_shaman,
+        make_orc_warlord,
+        make_player,
+        make_rat,
+        make_troll,
+    };
+    use crate::faction::Faction;
+    use crate::input::{player_move_or_attack, toggle_torch};
+    use crate::map::Map;
+    use crate::terrain::Terrain;
+    use crate::utils::seeded_rng;
+
+    /// `#` are walls, `@` the player, `o` orcs with the given behaviour, `T`
+    /// basic trolls, `r` cave rats and `d` dogs following the player.
+    /// `g` goblins, `a` orc archers, `s` orc shamans, `O` orc warlords and
+    /// `D` cave drakes behave as usual, except drakes are awake.
+    /// `+` are closed doors and `^` lava.
+    /// The player is easy to notice, so what monsters do does not depend
+    /// on luck.
+    fn fixture(rows: &[&str], ai: Ai) -> GameState {
+        let mut map = Map::new(rows[0].len() as i32, rows.len() as i32);
+        let mut elements = vec![make_player(0, 0)];
+        elements[PLAYER].stealth = 0;
+        for (y, row) in rows.iter().enumerate() {
+            for (x, c) in row.chars().enumerate() {
+                let (x, y) = (x as i32, y as i32);
+                if c != '#' {
+                    map.set_terrain(x, y, Terrain::Floor);
+                }
+                match c {
+                    '@' => elements[PLAYER].set_pos(x, y),
+                    'o' => {
+                        let mut orc = make_orc(x, y);
+                        orc.ai = Some(ai.clone());
+                        elements.push(orc);
+                    }
+                    'T' => {
+                        let mut troll = make_troll(x, y);
+                        troll.ai = Some(Ai::Basic);
+                        elements.push(troll);
+                    }
+                    '+' => map.set_terrain(x, y, Terrain::Door),
+                    '^' => map.set_terrain(x, y, Terrain::Lava),
+                    'r' => elements.push(make_rat(x, y)),
+                    'd' => elements.push(make_dog(x, y)),
+                    'g' => elements.push(make_goblin(x, y)),
+                    's' => elements.push(make_orc_shaman(x, y)),
+                    'a' => elements.push(make_orc_archer(x, y)),
+                    'O' => elements.push(make_orc_warlord(x, y)),
+                    'D' => {
+                        let mut drake = make_drake(x, y);
+                        drake.ai = Some(Ai::Basic);
+                        elements.push(drake);
+                    }
+                    _ => {}
+                }
+            }
+        }
+        GameState::new(map, elements, seeded_rng(0))
+    }
+
+    fn play_turn(game_state: &mut GameState) {
+        game_state.update_fov();
+        game_state.update_ai_maps();
+        for id in 0..game_state.elements.len() {
+            if game_state.elements[id].ai.is_some() {
+                ai_take_turn(id, game_state);
+            }
+        }
+    }
+
+    /// put the given elements in a pack, led by the first one
+    fn form_pack(game_state: &mut GameState, ids: &[usize]) {
+        for &id in ids {
+            game_state.elements[id].pack = Some(PackMember { pack: 0, leader: id == ids[0] });
+        }
+    }
+
+    const SEALED_ROOMS : [&str; 5] = [
+        "###########",
+        "#o....@...#",
+        "###########",
+        "#.........#",
+        "###########",
+    ];
+
+    #[test]
+    fn monster_walks_to_a_player_it_sees() {
+        let mut game_state = fixture(&SEALED_ROOMS, Ai::Basic);
+        play_turn(&mut game_state);
+        assert_eq!(game_state.elements[1].pos(), (2, 1));
+        assert_eq!(game_state.elements[1].last_seen.map(|l| (l.x, l.y)), Some((6, 1)));
+    }
+
+    #[test]
+    fn monster_searches_where_it_lost_the_player_then_gives_up() {
+        let mut game_state = fixture(&SEALED_ROOMS, Ai::Basic);
+        play_turn(&mut game_state);
+        // the player disappears into the other room
+        game_state.elements[PLAYER].set_pos(1, 3);
+        for _ in 0..4 {
+            play_turn(&mut game_state);
+        }
+        assert_eq!(game_state.elements[1].pos(), (6, 1));
+
+        for _ in 0..(SEARCH_TURNS + 1) {
+            assert!(game_state.elements[1].last_seen.is_some());
+            play_turn(&mut game_state);
+        }
+        assert_eq!(game_state.elements[1].last_seen, None);
+
+        // back to idling
+        let position = game_state.elements[1].pos();
+        play_turn(&mut game_state);
+        assert_eq!(game_state.elements[1].pos(), position);
+    }
+
+    #[test]
+    fn players_in_the_dark_are_only_noticed_from_close() {
+        let rows = [
+            "#########",
+            "#o...@..#",
+            "#########",
+        ];
+        let mut game_state = fixture(&rows, Ai::Basic);
+        toggle_torch(&mut game_state);
+        assert_eq!(game_state.elements[PLAYER].light, None);
+        play_turn(&mut game_state);
+        assert_eq!(game_state.elements[1].pos(), (1, 1));
+        assert_eq!(game_state.elements[1].last_seen, None);
+
+        game_state.elements[PLAYER].set_pos(2, 1);
+        play_turn(&mut game_state);
+        assert!(game_state.elements[1].last_seen.is_some());
+    }
+
+    #[test]
+    fn confused_monsters_stumble_instead_of_attacking() {
+        let rows = [
+            "####",
+            "#@o#",
+            "####",
+        ];
+        let mut game_state = fixture(&rows, Ai::Basic);
+        status::afflict(&mut game_state.elements[1], StatusKind::Confused, 2, 0, &mut vec![]);
+        for _ in 0..5 {
+            play_turn(&mut game_state);
+        }
+        assert_eq!(hp(&game_state, PLAYER), 30);
+        for _ in 0..2 {
+            status::tick(1, &mut game_state);
+        }
+        assert!(game_state.elements[1].statuses.is_empty());
+        assert_eq!(game_state.log.last().map(|(message, _)| message.as_str()),
+                   Some("The orc is no longer confused."));
+    }
+
+    #[test]
+    fn invisible_players_are_only_noticed_from_next_to_them() {
+        let mut game_state = fixture(&SEALED_ROOMS, Ai::Basic);
+        status::afflict(&mut game_state.elements[PLAYER], StatusKind::Invisible, 20, 0, &mut vec![]);
+        play_turn(&mut game_state);
+        assert_eq!(game_state.elements[1].last_seen, None);
+
+        game_state.elements[PLAYER].set_pos(2, 1);
+        play_turn(&mut game_state);
+        assert!(game_state.elements[1].last_seen.is_some());
+    }
+
+    #[test]
+    fn monsters_fight_other_factions_but_leave_neutrals_alone() {
+        let rows = [
+            "#######",
+            "#@#roT#",
+            "#######",
+        ];
+        let mut game_state = fixture(&rows, Ai::Basic);
+        for _ in 0..5 {
+            play_turn(&mut game_state);
+        }
+        assert_eq!(hp(&game_state, 1), 3);
+        // the troll got the better of the orc
+        assert_eq!(hp(&game_state, 2), 0);
+        assert!(!game_state.elements[2].alive);
+        assert_eq!(hp(&game_state, 3), 8);
+        // the player saw none of it
+        assert!(game_state.log.is_empty());
+    }
+
+    #[test]
+    fn messages_name_both_sides() {
+        let rows = [
+            "#######",
+            "#@.oT.#",
+            "#######",
+        ];
+        let mut game_state = fixture(&rows, Ai::Basic);
+        play_turn(&mut game_state);
+        let messages: Vec<_> = game_state.log.iter().map(|(message, _)| message.as_str()).collect();
+        assert_eq!(messages, [
+            "The orc attacks the troll but misses.",
+            "The troll attacks the orc for 3 hit points.",
+        ]);
+    }
+
+    #[test]
+    fn followers_come_back_to_the_player_and_defend_them() {
+        let rows = [
+            "##########",
+            "#@......d#",
+            "##########",
+        ];
+        let mut game_state = fixture(&rows, Ai::Basic);
+        play_turn(&mut game_state);
+        assert_eq!(game_state.elements[1].pos(), (7, 1));
+
+        let rows = [
+            "#######",
+            "#@d.o.#",
+            "#######",
+        ];
+        let mut game_state = fixture(&rows, Ai::Sleeping);
+        play_turn(&mut game_state);
+        assert_eq!(game_state.elements[1].pos(), (3, 1));
+        play_turn(&mut game_state);
+        assert!(game_state.elements[2].fighter.is_some_and(|f| f.hp < f.max_hp));
+    }
+
+    #[test]
+    fn players_swap_places_with_their_allies() {
+        let rows = [
+            "#####",
+            "#@d.#",
+            "#####",
+        ];
+        let mut game_state = fixture(&rows, Ai::Basic);
+        player_move_or_attack(&mut game_state, 1, 0);
+        assert_eq!(game_state.elements[PLAYER].pos(), (2, 1));
+        assert_eq!(game_state.elements[1].pos(), (1, 1));
+        assert_eq!(hp(&game_state, 1), game_state.elements[1].fighter.unwrap().max_hp);
+    }
+
+    #[test]
+    fn close_allies_follow_the_player_downstairs() {
+        let rows = [
+            "############",
+            "#@d.......d#",
+            "############",
+        ];
+        let mut game_state = fixture(&rows, Ai::Basic);
+        game_state.descend();
+        assert_eq!(game_state.dungeon_level, 2);
+        let dogs: Vec<_> = game_state.elements.iter()
+            .filter(|element| element.faction == Faction::Player && !element.is_player())
+            .collect();
+        assert_eq!(dogs.len(), 1);
+        assert!(dogs[0].distance_to(&game_state.elements[PLAYER]) < 2.0);
+        assert_eq!(dogs[0].ai, Some(Ai::Follower));
+    }
+
+    #[test]
+    fn pack_members_share_what_they_see() {
+        let rows = [
+            "###########",
+            "#o....@...#",
+            "###########",
+            "#o........#",
+            "###########",
+        ];
+        let mut game_state = fixture(&rows, Ai::Sleeping);
+        form_pack(&mut game_state, &[1, 2]);
+        play_turn(&mut game_state);
+        assert_eq!(game_state.elements[2].ai, Some(Ai::Basic));
+        assert_eq!(game_state.elements[2].last_seen.map(|l| (l.x, l.y)), Some((6, 1)));
+    }
+
+    #[test]
+    fn sleepers_only_alert_their_pack_once_awake() {
+        let mut game_state = fixture(&SEALED_ROOMS, Ai::Sleeping);
+        game_state.elements.push(make_orc(1, 3));
+        game_state.elements[2].ai = Some(Ai::Sleeping);
+        form_pack(&mut game_state, &[1, 2]);
+        // a seed where the first orc sleeps on for a while after noticing
+        game_state.rng = seeded_rng(6);
+        let mut turns_asleep = 0;
+        while game_state.elements[1].ai == Some(Ai::Sleeping) {
+            play_turn(&mut game_state);
+            turns_asleep += 1;
+            if game_state.elements[1].ai == Some(Ai::Sleeping) {
+                assert!(game_state.elements[1].last_seen.is_some());
+                // its mate knows nothing
+                assert_eq!(game_state.elements[2].ai, Some(Ai::Sleeping));
+                assert_eq!(game_state.elements[2].last_seen, None);
+            }
+        }
+        assert!(turns_asleep > 1);
+        assert_eq!(game_state.elements[2].ai, Some(Ai::Basic));
+    }
+
+    #[test]
+    fn pack_members_surround_their_target() {
+        let rows = [
+            "#######",
+            "#.....#",
+            "#@.oo.#",
+            "#.....#",
+            "#######",
+        ];
+        let mut game_state = fixture(&rows, Ai::Basic);
+        form_pack(&mut game_state, &[1, 2]);
+        play_turn(&mut game_state);
+        // the second orc does not wait behind the first one
+        assert_ne!(game_state.elements[2].pos(), (3, 2));
+        play_turn(&mut game_state);
+        for id in 1..3 {
+            assert!(game_state.elements[id].distance_to(&game_state.elements[PLAYER]) < 2.0);
+        }
+    }
+
+    #[test]
+    fn pack_runs_away_once_its_leader_is_dead() {
+        let rows = [
+            "###########",
+            "#@.oo.o...#",
+            "###########",
+        ];
+        let mut game_state = fixture(&rows, Ai::Basic);
+        form_pack(&mut game_state, &[1, 2]);
+        game_state.elements[1].take_damage(100, DamageType::Physical, &mut vec![]);
+        play_turn(&mut game_state);
+        assert!(game_state.elements[2].pos().0 > 4);
+        assert!(matches!(game_state.elements[2].ai, Some(Ai::Routed { .. })));
+        // monsters outside the pack do not care
+        assert_eq!(game_state.elements[3].ai, Some(Ai::Basic));
+    }
+
+    fn hp(game_state: &GameState, id: usize) -> i32 {
+        game_state.elements[id].fighter.map_or(0, |f| f.hp)
+    }
+
+    #[test]
+    fn archers_shoot_arrows_from_afar() {
+        let rows = [
+            "#########",
+            "#@.....a#",
+            "#########",
+        ];
+        let mut game_state = fixture(&rows, Ai::Basic);
+        for _ in 0..3 {
+            play_turn(&mut game_state);
+        }
+        assert_eq!(game_state.elements[1].pos(), (7, 1));
+        let messages: Vec<_> = game_state.log.iter().map(|(message, _)| message.as_str()).collect();
+        assert_eq!(messages, [
+            "The arrow hits you for 5 hit points.",
+            "The arrow hits you for 2 hit points.",
+            "The arrow misses you.",
+        ]);
+    }
+
+    #[test]
+    fn shamans_cast_spells_from_afar_and_heal_when_hurt() {
+        let rows = [
+            "#########",
+            "#@....s.#",
+            "#########",
+        ];
+        let mut game_state = fixture(&rows, Ai::Basic);
+        play_turn(&mut game_state);
+        play_turn(&mut game_state);
+        assert!(status::has(&game_state.elements[PLAYER], StatusKind::Slowed));
+        assert_eq!(hp(&game_state, PLAYER), 25);
+        let messages: Vec<_> = game_state.log.iter().map(|(message, _)| message.as_str()).collect();
+        assert_eq!(messages, [
+            "The orc shaman casts a hex!",
+            "You are slowed!",
+            "The orc shaman casts a lightning bolt!",
+            "The lightning bolt hits you for 5 hit points.",
+        ]);
+
+        game_state.elements[1].fighter.as_mut().unwrap().hp = 3;
+        play_turn(&mut game_state);
+        assert_eq!(hp(&game_state, 1), 8);
+        // the bolt is not ready yet anyway
+        assert_eq!(hp(&game_state, PLAYER), 25);
+    }
+
+    #[test]
+    fn breath_hits_everyone_in_its_cone() {
+        let rows = [
+            "#######",
+            "#@d..D#",
+            "#######",
+        ];
+        let mut game_state = fixture(&rows, Ai::Basic);
+        play_turn(&mut game_state);
+        assert_eq!(hp(&game_state, PLAYER), 24);
+        assert_eq!(hp(&game_state, 1), 6);
+    }
+
+    #[test]
+    fn hurt_goblins_blink_away() {
+        let rows = [
+            "###########",
+            "#@g.......#",
+            "###########",
+        ];
+        let mut game_state = fixture(&rows, Ai::Basic);
+        game_state.elements[1].fighter.as_mut().unwrap().hp = 2;
+        play_turn(&mut game_state);
+        assert!(game_state.elements[1].pos().0 >= 6);
+        assert!(!game_state.elements[1].abilities[0].is_ready());
+    }
+
+    #[test]
+    fn goblins_do_not_blink_into_doors_or_lava() {
+        let rows = [
+            "#########",
+            "#@g...+.#",
+            "#.....^.#",
+            "#########",
+        ];
+        let mut game_state = fixture(&rows, Ai::Basic);
+        game_state.elements[1].fighter.as_mut().unwrap().hp = 2;
+        play_turn(&mut game_state);
+        assert_eq!(game_state.elements[1].pos(), (5, 2));
+    }
+
+    #[test]
+    fn warlords_call_for_help() {
+        let rows = [
+            "#########",
+            "#@....O.#",
+            "#########",
+        ];
+        let mut game_state = fixture(&rows, Ai::Basic);
+        play_turn(&mut game_state);
+        assert_eq!(game_state.elements.len(), 4);
+        for id in 2..4 {
+            assert_eq!(game_state.elements[id].display_name, "orc");
+            assert!(game_state.elements[id].last_seen.is_some());
         }
     }
 }
@@ -0,0 +1,88 @@
+//! Preview generated levels in the terminal, without launching the game.
+//!
+//! Usage: mapgen [--seed N] [--kind rooms|caves] [--count N]
+extern crate roguelike_test;
+
+use roguelike_test::{
+    constants::{MAP_HEIGHT, MAP_WIDTH},
+    element::{Element, make_player},
+    map::{GeneratorKind, generate_map, is_connected},
+    terrain::Terrain,
+    utils::seeded_rng,
+};
+use std::env;
+use std::process;
+
+struct Options {
+    seed: usize,
+    kind: GeneratorKind,
+    count: usize,
+}
+
+fn usage() -> ! {
+    eprintln!("Usage: mapgen [--seed N] [--kind rooms|caves] [--count N]");
+    process::exit(1);
+}
+
+fn parse_options() -> Options {
+    let mut options = Options { seed: 0, kind: GeneratorKind::Rooms, count: 1 };
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let value = args.next().unwrap_or_else(|| usage());
+        match arg.as_str() {
+            "--seed" => options.seed = value.parse().unwrap_or_else(|_| usage()),
+            "--kind" => options.kind = GeneratorKind::from_name(&value).unwrap_or_else(|| usage()),
+            "--count" => options.count = value.parse().unwrap_or_else(|_| usage()),
+            _ => usage(),
+        }
+    }
+    options
+}
+
+fn main() {
+    let options = parse_options();
+    // a single generator for all levels, like a game going down the dungeon
+    let mut rng = seeded_rng(options.seed);
+
+    for level in 0..options.count {
+        let mut elements: Vec<Element> = vec![make_player(0, 0)];
+        let generated = generate_map(options.kind, &mut rng, &mut elements);
+        let map = &generated.map;
+        let (start_x, start_y) = generated.starting_position;
+        elements[0].set_pos(start_x, start_y);
+
+        println!("Level {} (seed {}, {:?})", level + 1, options.seed, options.kind);
+        for y in 0..MAP_HEIGHT {
+            let line: String = (0..MAP_WIDTH).map(|x| {
+                // draw blocking elements over the items they may stand on
+                let on_tile = elements.iter()
+                    .filter(|e| e.pos() == (x, y))
+                    .max_by_key(|e| e.block_movement);
+                match on_tile {
+                    Some(element) => element.char,
+                    None => match map[x as usize][y as usize].terrain {
+                        Terrain::Wall => '#',
+                        Terrain::Floor => '.',
+                        Terrain::DeepWater => '=',
+                        Terrain::Lava => '^',
+                        terrain => terrain.info().glyph,
+                    },
+                }
+            }).collect();
+            println!("{}", line);
+        }
+
+        let floor = map.tiles()
+            .filter(|tile| tile.is_passable())
+            .count();
+        let monsters = elements.iter().skip(1).filter(|e| e.ai.is_some()).count();
+        let items = elements.iter().filter(|e| e.item.is_some()).count();
+        println!("rooms: {}, floor: {:.1}%, monsters: {}, items: {}, connected: {}",
+                 generated.rooms.len(),
+                 floor as f32 * 100.0 / (MAP_WIDTH * MAP_HEIGHT) as f32,
+                 monsters,
+                 items,
+                 if is_connected(map, generated.starting_position) { "yes" } else { "no" });
+        println!();
+    }
+}
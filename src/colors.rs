@@ -4,14 +4,44 @@ pub const COLOR_DARK_WALL: Color = Color { r: 10, g: 5, b: 5 };
 pub const COLOR_LIGHT_WALL: Color = Color { r: 40, g: 15, b: 15 };
 pub const COLOR_DARK_GROUND: Color = Color { r: 50, g: 32, b: 32 };
 pub const COLOR_LIGHT_GROUND: Color = Color { r: 60, g: 42, b: 32 };
+pub const COLOR_DOOR: Color = Color { r: 150, g: 100, b: 50 };
+pub const COLOR_WATER_GLYPH: Color = Color { r: 120, g: 150, b: 220 };
+pub const COLOR_LIGHT_WATER: Color = Color { r: 30, g: 50, b: 110 };
+pub const COLOR_DARK_WATER: Color = Color { r: 15, g: 25, b: 55 };
+pub const COLOR_LIGHT_DEEP_WATER: Color = Color { r: 15, g: 25, b: 80 };
+pub const COLOR_DARK_DEEP_WATER: Color = Color { r: 5, g: 10, b: 40 };
+pub const COLOR_LAVA_GLYPH: Color = Color { r: 255, g: 200, b: 50 };
+pub const COLOR_LIGHT_LAVA: Color = Color { r: 200, g: 60, b: 10 };
+pub const COLOR_DARK_LAVA: Color = Color { r: 90, g: 25, b: 5 };
+pub const COLOR_RUBBLE: Color = Color { r: 130, g: 120, b: 110 };
+pub const COLOR_GRASS: Color = Color { r: 90, g: 140, b: 60 };
+pub const COLOR_STAIRS: Color = colors::WHITE;
+pub const COLOR_BRAZIER: Color = Color { r: 255, g: 140, b: 30 };
+pub const COLOR_FUNGUS: Color = Color { r: 80, g: 220, b: 200 };
+
+pub const COLOR_TORCH: Color = Color { r: 255, g: 210, b: 150 };
+pub const COLOR_BRAZIER_LIGHT: Color = Color { r: 255, g: 120, b: 40 };
+pub const COLOR_LAVA_LIGHT: Color = Color { r: 200, g: 70, b: 10 };
+pub const COLOR_FUNGUS_LIGHT: Color = Color { r: 40, g: 160, b: 150 };
 
 pub const COLOR_PLAYER: Color = colors::WHITE;
 pub const COLOR_PLAYER_DEAD: Color = colors::DARK_RED;
 
 pub const COLOR_MONSTER_ORC: Color = colors::DESATURATED_GREEN;
 pub const COLOR_MONSTER_TROLL: Color = colors::DARKER_GREEN;
+pub const COLOR_MONSTER_GOBLIN: Color = colors::LIGHT_GREEN;
+pub const COLOR_MONSTER_RAT: Color = colors::LIGHT_SEPIA;
+pub const COLOR_MONSTER_ORC_ARCHER: Color = colors::DESATURATED_YELLOW;
+pub const COLOR_MONSTER_ORC_SHAMAN: Color = colors::LIGHT_VIOLET;
+pub const COLOR_MONSTER_ORC_WARLORD: Color = colors::DARK_GREEN;
+pub const COLOR_MONSTER_DRAKE: Color = colors::FLAME;
+pub const COLOR_ALLY_DOG: Color = colors::LIGHTER_AMBER;
 pub const COLOR_MONSTER_DEAD: Color = colors::DARK_RED;
 pub const COLOR_POTION: Color = colors::VIOLET;
+pub const COLOR_SCROLL: Color = colors::LIGHT_YELLOW;
 
 pub const COLOR_HP_FOREGROUND: Color = colors::LIGHT_RED;
 pub const COLOR_HP_BACKGROUND: Color = colors::DARKER_RED;
+pub const COLOR_XP_FOREGROUND: Color = colors::LIGHT_YELLOW;
+pub const COLOR_XP_BACKGROUND: Color = colors::DARKER_YELLOW;
+pub const COLOR_LEVEL_UP: Color = colors::YELLOW;
@@ -1,3 +1,5 @@
+use crate::fov::FovAlgorithm;
+
 // sizes and coordinates relevant for the GUI
 pub const SCREEN_WIDTH: i32 = 80;
 pub const SCREEN_HEIGHT: i32 = 50;
@@ -16,14 +18,105 @@ pub const ROOM_MAX_SIZE: i32 = 10;
 pub const ROOM_MIN_SIZE: i32 = 6;
 pub const MAX_ROOMS: i32 = 30;
 
+/// chance for each room to be replaced by one of the vaults of `vaults.txt`
+pub const VAULT_CHANCE: f32 = 0.15;
+
+pub const CAVE_INITIAL_WALL_CHANCE: f32 = 0.45;
+pub const CAVE_SMOOTHING_STEPS: i32 = 4;
+pub const CAVE_FUNGUS_CHANCE: f32 = 0.01;
+
 pub const MAX_ROOM_MONSTERS: i32 = 3;
 pub const MAX_ROOM_ITEMS: i32 = 2;
 
+pub const TORCH_BEARER_CHANCE: f32 = 0.2;
+
 // misc
 pub const LIMIT_FPS: i32 = 20;
 
+/// how far the player can see tiles which are lit
+pub const SIGHT_RADIUS: i32 = 20;
+/// how far blinded players can still see
+pub const BLINDED_SIGHT_RADIUS: i32 = 1;
+pub const FOV_ALGORITHM: FovAlgorithm = FovAlgorithm::SymmetricShadowcasting;
 pub const TORCH_RADIUS: i32 = 8;
+pub const MONSTER_TORCH_RADIUS: i32 = 5;
+
+/// attacks roll this die plus the attacker's accuracy, and hit when
+/// reaching `BASE_EVASION` plus the defender's evasion
+pub const ATTACK_DIE: i32 = 20;
+pub const BASE_EVASION: i32 = 10;
+/// attacks missing by at most this much still graze the defender
+pub const GRAZE_MARGIN: i32 = 3;
+
+/// turns a poisonous attack keeps hurting for
+pub const POISON_TURNS: i32 = 4;
+/// turns the statuses given by potions and scrolls last
+pub const HASTE_TURNS: i32 = 10;
+pub const REGENERATION_TURNS: i32 = 10;
+/// hit points a potion of regeneration heals each turn
+pub const REGENERATION_POTENCY: i32 = 2;
+pub const INVISIBILITY_TURNS: i32 = 15;
+pub const CONFUSE_TURNS: i32 = 8;
+pub const BLIND_TURNS: i32 = 5;
+pub const STUN_TURNS: i32 = 2;
+/// how far the target of a scroll of confusion can be
+pub const CONFUSE_RANGE: f32 = 8.0;
+/// turns for a fighter to heal a hit point on its own, unless poisoned
+pub const REGEN_TURNS: u32 = 10;
+
+/// energy an element needs to act, and what a normal action costs
+pub const TURN_ENERGY: i32 = 100;
+/// energy gained per turn by elements of normal speed
+pub const NORMAL_SPEED: i32 = 100;
+/// turns are divided in ticks, so cheaper actions leave less time to others
+pub const TICKS_PER_TURN: i32 = 10;
+/// energy spent walking, per turn needed to walk on the tile
+pub const MOVE_COST: i32 = 100;
+pub const ATTACK_COST: i32 = 100;
+pub const USE_ITEM_COST: i32 = 50;
+pub const ABILITY_COST: i32 = 100;
+
+/// whether monsters may walk diagonally when following a path
+pub const MONSTER_DIAGONAL_MOVES: bool = true;
+/// extra cost of paths through harmful terrain, so they go around it
+pub const HARMFUL_TERRAIN_COST: i32 = 20;
+
+/// turns a monster searches around where it lost sight of the player
+pub const SEARCH_TURNS: i32 = 5;
+/// chance a sleeping monster wakes up when it notices the player
+pub const WAKE_UP_CHANCE: f32 = 0.5;
+/// ranged monsters back away from the player when closer than this
+pub const RANGED_MIN_DISTANCE: f32 = 3.0;
+
+/// monsters heal allies, themselves included, below this fraction of
+/// their hit points
+pub const HEAL_ALLY_BELOW: f32 = 0.5;
+/// monsters below this fraction of their hit points blink away from
+/// their enemies
+pub const BLINK_AWAY_BELOW: f32 = 0.5;
+
+/// turns pack members run away for once their leader is dead
+pub const ROUT_TURNS: i32 = 10;
+
+/// followers come back to the player when farther than this
+pub const FOLLOW_DISTANCE: f32 = 2.5;
+/// followers only go after enemies this close to the player
+pub const FOLLOWER_LEASH: f32 = 6.0;
+/// allies this close to the player follow them down the stairs
+pub const FOLLOW_DOWN_DISTANCE: f32 = 4.0;
+
+/// sight radiuses are divided by this when looking at an unlit tile
+pub const DARKNESS_SIGHT_DIVISOR: i32 = 3;
+/// chance lost to notice someone at the edge of sight, per stealth point
+pub const STEALTH_FACTOR: f32 = 0.15;
 
 pub const PLAYER: usize = 0;
 
 pub const INVENTORY_WIDTH : i32 = 50;
+pub const LEVEL_SCREEN_WIDTH: i32 = 40;
+
+/// experience needed to reach level 2, then more for each level after
+pub const LEVEL_UP_BASE: i32 = 200;
+pub const LEVEL_UP_FACTOR: i32 = 150;
+/// maximum hit points gained when choosing them at a level up
+pub const LEVEL_UP_HP: i32 = 20;
@@ -0,0 +1,72 @@
+//! Kinds of damage, and how well fighters stand each of them.
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DamageType {
+    /// the only kind armour protects from
+    Physical,
+    Fire,
+    Cold,
+    Poison,
+    Lightning,
+}
+
+const DAMAGE_TYPES: usize = 5;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Resistance {
+    Normal,
+    /// takes half the damage
+    Resistant,
+    /// takes twice the damage
+    Vulnerable,
+    /// takes no damage at all
+    Immune,
+}
+
+impl Resistance {
+    pub fn apply(self, damage: i32) -> i32 {
+        match self {
+            Resistance::Normal => damage,
+            Resistance::Resistant => damage / 2,
+            Resistance::Vulnerable => damage * 2,
+            Resistance::Immune => 0,
+        }
+    }
+}
+
+/// A fighter's resistance to each type of damage
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Resistances([Resistance; DAMAGE_TYPES]);
+
+impl Resistances {
+    pub fn none() -> Self {
+        Resistances([Resistance::Normal; DAMAGE_TYPES])
+    }
+
+    pub fn with(mut self, damage_type: DamageType, resistance: Resistance) -> Self {
+        self.0[damage_type as usize] = resistance;
+        self
+    }
+
+    pub fn of(&self, damage_type: DamageType) -> Resistance {
+        self.0[damage_type as usize]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resistances_only_change_their_own_type() {
+        let resistances = Resistances::none()
+            .with(DamageType::Fire, Resistance::Immune)
+            .with(DamageType::Cold, Resistance::Vulnerable)
+            .with(DamageType::Poison, Resistance::Resistant);
+        assert_eq!(resistances.of(DamageType::Fire).apply(7), 0);
+        assert_eq!(resistances.of(DamageType::Cold).apply(7), 14);
+        assert_eq!(resistances.of(DamageType::Poison).apply(7), 3);
+        assert_eq!(resistances.of(DamageType::Physical).apply(7), 7);
+        assert_eq!(resistances.of(DamageType::Lightning).apply(7), 7);
+    }
+}
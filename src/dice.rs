@@ -0,0 +1,68 @@
+//! Dice rolls, such as the damage of an attack: "2d4+1" is the sum of two
+//! four-sided dice, plus one.
+use crate::utils::GameRng;
+use rand::Rng;
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Dice {
+    pub count: i32,
+    pub sides: i32,
+    pub bonus: i32,
+}
+
+impl Dice {
+    pub fn new(count: i32, sides: i32, bonus: i32) -> Self {
+        Dice { count, sides, bonus }
+    }
+
+    pub fn roll(&self, rng: &mut GameRng) -> i32 {
+        let rolls: i32 = (0..self.count).map(|_| rng.gen_range(1, self.sides + 1)).sum();
+        rolls + self.bonus
+    }
+}
+
+impl fmt::Display for Dice {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}d{}", self.count, self.sides)?;
+        if self.bonus > 0 {
+            write!(f, "+{}", self.bonus)?;
+        } else if self.bonus < 0 {
+            write!(f, "{}", self.bonus)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::seeded_rng;
+
+    #[test]
+    fn rolls_stay_within_the_dice() {
+        let dice = Dice::new(2, 4, 1);
+        let mut rng = seeded_rng(0);
+        let rolls: Vec<_> = (0..200).map(|_| dice.roll(&mut rng)).collect();
+        assert!(rolls.iter().all(|roll| (3..=9).contains(roll)));
+        assert!(rolls.contains(&3));
+        assert!(rolls.contains(&9));
+    }
+
+    #[test]
+    fn same_seed_same_rolls() {
+        let dice = Dice::new(1, 20, 0);
+        let roll_all = |seed| {
+            let mut rng = seeded_rng(seed);
+            (0..20).map(|_| dice.roll(&mut rng)).collect::<Vec<_>>()
+        };
+        assert_eq!(roll_all(3), roll_all(3));
+    }
+
+    #[test]
+    fn dice_read_as_usual() {
+        assert_eq!(Dice::new(2, 4, 1).to_string(), "2d4+1");
+        assert_eq!(Dice::new(1, 6, 0).to_string(), "1d6");
+        assert_eq!(Dice::new(1, 3, -1).to_string(), "1d3-1");
+    }
+}
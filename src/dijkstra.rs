@@ -0,0 +1,271 @@
+//! Dijkstra maps: the cost of walking from every tile to the nearest of
+//! some goals. Walking downhill on one leads to a goal by the cheapest path,
+//! whoever walks it, so a single map per turn serves every monster.
+use crate::element::Element;
+use crate::map::Map;
+use crate::pathfinding::{DIAGONAL_MOVES, ORTHOGONAL_MOVES};
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+/// value of the tiles no goal can be reached from
+pub const UNREACHABLE: i32 = i32::MAX;
+
+/// How much farther than the goals tiles are considered safe when fleeing.
+/// Above 1, it makes fleeing elements run past the goals rather than into
+/// dead ends.
+const FLEE_FACTOR: f32 = 1.2;
+
+#[derive(Clone, Debug)]
+pub struct DijkstraMap {
+    width: i32,
+    height: i32,
+    diagonals: bool,
+    values: Vec<i32>,
+}
+
+impl DijkstraMap {
+    pub fn new(width: i32, height: i32, diagonals: bool) -> Self {
+        DijkstraMap {
+            width,
+            height,
+            diagonals,
+            values: vec![UNREACHABLE; (width * height) as usize],
+        }
+    }
+
+    /// Recompute the cost of reaching the nearest goal from every tile,
+    /// along the terrain's movement costs.
+    pub fn compute(&mut self, map: &Map, goals: &[(i32, i32)]) {
+        let seeds: Vec<_> = goals.iter().map(|&goal| (goal, 0)).collect();
+        self.propagate(map, seeds);
+    }
+
+    /// A map leading away from the goals of this one, going around them
+    /// when needed instead of getting cornered.
+    pub fn fleeing(&self, map: &Map) -> DijkstraMap {
+        let seeds = self.cells()
+            .filter(|&(x, y)| self.value(x, y) != UNREACHABLE)
+            .map(|(x, y)| ((x, y), -(self.value(x, y) as f32 * FLEE_FACTOR).round() as i32))
+            .collect();
+        let mut fleeing = DijkstraMap::new(self.width, self.height, self.diagonals);
+        fleeing.propagate(map, seeds);
+        fleeing
+    }
+
+    /// cost of reaching the nearest goal from the given tile, None if none
+    /// can be reached
+    pub fn distance(&self, x: i32, y: i32) -> Option<i32> {
+        Some(self.value(x, y)).filter(|&value| value != UNREACHABLE)
+    }
+
+    /// The step an element should take to go downhill, avoiding tiles other
+    /// elements block. None if it is already as low as it can go.
+    pub fn next_step(&self, mover_id: usize, map: &Map, elements: &[Element]) -> Option<(i32, i32)> {
+        let (x, y) = elements[mover_id].pos();
+        let mut best = ((x, y), self.value(x, y));
+        for &(dx, dy) in self.moves() {
+            let (next_x, next_y) = (x + dx, y + dy);
+            if !map.is_in_bounds(next_x, next_y) || map.cuts_corner(x, y, dx, dy) {
+                continue;
+            }
+            let occupied = elements.iter().enumerate().any(|(id, element)| {
+                id != mover_id && element.block_movement && element.pos() == (next_x, next_y)
+            });
+            let value = self.value(next_x, next_y);
+            if !occupied && value < best.1 {
+                best = ((next_x, next_y), value);
+            }
+        }
+        Some(best.0).filter(|&position| position != (x, y))
+    }
+
+    fn value(&self, x: i32, y: i32) -> i32 {
+        self.values[(x * self.height + y) as usize]
+    }
+
+    fn cells(&self) -> impl Iterator<Item = (i32, i32)> {
+        let height = self.height;
+        (0..self.width).flat_map(move |x| (0..height).map(move |y| (x, y)))
+    }
+
+    fn moves(&self) -> impl Iterator<Item = &(i32, i32)> {
+        let diagonals = self.diagonals;
+        ORTHOGONAL_MOVES.iter().chain(DIAGONAL_MOVES.iter().filter(move |_| diagonals))
+    }
+
+    /// Set the given tiles to the given values, then every other tile to the
+    /// lowest value it can reach plus the cost of walking there.
+    fn propagate(&mut self, map: &Map, seeds: Vec<((i32, i32), i32)>) {
+        for value in self.values.iter_mut() {
+            *value = UNREACHABLE;
+        }
+        let mut open = BinaryHeap::new();
+        for ((x, y), value) in seeds {
+            let index = (x * self.height + y) as usize;
+            if value < self.values[index] {
+                self.values[index] = value;
+                open.push(Reverse((value, (x, y))));
+            }
+        }
+
+        while let Some(Reverse((value, (x, y)))) = open.pop() {
+            if value > self.value(x, y) {
+                // already reached for cheaper
+                continue;
+            }
+            // walking from a neighbour to this tile costs this tile's cost
+            let cost = map[x as usize][y as usize].movement_cost().unwrap_or(1);
+            let moves: Vec<_> = self.moves().cloned().collect();
+            for (dx, dy) in moves {
+                let (next_x, next_y) = (x + dx, y + dy);
+                if !map.is_in_bounds(next_x, next_y) ||
+                    map[next_x as usize][next_y as usize].movement_cost().is_none() ||
+                    map.cuts_corner(x, y, dx, dy) {
+                    continue;
+                }
+                let index = (next_x * self.height + next_y) as usize;
+                if value + cost < self.values[index] {
+                    self.values[index] = value + cost;
+                    open.push(Reverse((value + cost, (next_x, next_y))));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::element::make_orc;
+    use crate::terrain::Terrain;
+
+    /// `#` are walls, `~` shallow water, `^` lava, `G` goals and `@` a monster
+    fn fixture(rows: &[&str]) -> (Map, Vec<(i32, i32)>, Vec<Element>) {
+        let mut map = Map::new(rows[0].len() as i32, rows.len() as i32);
+        let mut goals = vec![];
+        let mut elements = vec![];
+        for (y, row) in rows.iter().enumerate() {
+            for (x, c) in row.chars().enumerate() {
+                let (x, y) = (x as i32, y as i32);
+                match c {
+                    '#' => continue,
+                    '~' => map.set_terrain(x, y, Terrain::Water),
+                    '^' => map.set_terrain(x, y, Terrain::Lava),
+                    _ => map.set_terrain(x, y, Terrain::Floor),
+                }
+                match c {
+                    'G' => goals.push((x, y)),
+                    '@' => elements.push(make_orc(x, y)),
+                    _ => {}
+                }
+            }
+        }
+        (map, goals, elements)
+    }
+
+    fn compute(rows: &[&str], diagonals: bool) -> (DijkstraMap, Map, Vec<Element>) {
+        let (map, goals, elements) = fixture(rows);
+        let mut dijkstra_map = DijkstraMap::new(map.width(), map.height(), diagonals);
+        dijkstra_map.compute(&map, &goals);
+        (dijkstra_map, map, elements)
+    }
+
+    #[test]
+    fn distances_grow_from_the_goal() {
+        let rows = [
+            "#######",
+            "#G....#",
+            "#.....#",
+            "#######",
+        ];
+        let (orthogonal, _, _) = compute(&rows, false);
+        assert_eq!(orthogonal.distance(1, 1), Some(0));
+        assert_eq!(orthogonal.distance(5, 1), Some(4));
+        assert_eq!(orthogonal.distance(5, 2), Some(5));
+        assert_eq!(orthogonal.distance(0, 0), None);
+
+        let (diagonal, _, _) = compute(&rows, true);
+        assert_eq!(diagonal.distance(5, 2), Some(4));
+    }
+
+    #[test]
+    fn walls_are_walked_around() {
+        let rows = [
+            "#######",
+            "#G#...#",
+            "#.#.#.#",
+            "#...#.#",
+            "#######",
+        ];
+        let (dijkstra_map, _, _) = compute(&rows, false);
+        assert_eq!(dijkstra_map.distance(3, 1), Some(6));
+        assert_eq!(dijkstra_map.distance(5, 3), Some(10));
+    }
+
+    #[test]
+    fn movement_costs_are_respected() {
+        let rows = [
+            "#######",
+            "#G~~~.#",
+            "#.....#",
+            "#######",
+        ];
+        let (dijkstra_map, _, _) = compute(&rows, false);
+        // going around the water is cheaper than wading through it
+        assert_eq!(dijkstra_map.distance(5, 1), Some(6));
+        // stepping out of the water costs nothing more than on the floor
+        assert_eq!(dijkstra_map.distance(2, 1), Some(1));
+        assert_eq!(dijkstra_map.distance(3, 1), Some(3));
+    }
+
+    #[test]
+    fn harmful_terrain_is_walked_around() {
+        let rows = [
+            "#########",
+            "#G.^^^..#",
+            "#.......#",
+            "#########",
+        ];
+        let (dijkstra_map, _, _) = compute(&rows, false);
+        assert_eq!(dijkstra_map.distance(7, 1), Some(8));
+    }
+
+    #[test]
+    fn nearest_goal_wins() {
+        let rows = [
+            "#########",
+            "#G.....G#",
+            "#########",
+        ];
+        let (dijkstra_map, _, _) = compute(&rows, false);
+        assert_eq!(dijkstra_map.distance(3, 1), Some(2));
+        assert_eq!(dijkstra_map.distance(6, 1), Some(1));
+    }
+
+    #[test]
+    fn next_step_goes_downhill_around_others() {
+        let rows = [
+            "######",
+            "#G...#",
+            "#..@@#",
+            "######",
+        ];
+        let (dijkstra_map, map, elements) = compute(&rows, false);
+        assert_eq!(dijkstra_map.next_step(0, &map, &elements), Some((3, 1)));
+        // the other orc stands on the shortest way
+        assert_eq!(dijkstra_map.next_step(1, &map, &elements), Some((4, 1)));
+    }
+
+    #[test]
+    fn fleeing_leads_away_from_the_goal() {
+        let rows = [
+            "#########",
+            "#...G@..#",
+            "#########",
+        ];
+        let (dijkstra_map, map, elements) = compute(&rows, false);
+        let fleeing = dijkstra_map.fleeing(&map);
+        assert_eq!(fleeing.next_step(0, &map, &elements), Some((6, 1)));
+        assert!(fleeing.distance(7, 1) < fleeing.distance(5, 1));
+    }
+}
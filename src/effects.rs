@@ -0,0 +1,55 @@
+//! What items and monster abilities do to the elements they affect, so
+//! both deal damage and tell about it the same way.
+use crate::constants::PLAYER;
+use crate::damage::DamageType;
+use crate::element::{Element, capitalize};
+use crate::faction::Faction;
+use crate::messages::{MessageLog, Messages};
+use tcod::colors;
+
+/// Hurt the target with the given source of damage, such as "lightning
+/// bolt", killing it if it runs out of hit points.
+/// Returns the experience the target is worth if it died.
+pub fn damage(target: &mut Element,
+              source: &str,
+              amount: i32,
+              damage_type: DamageType,
+              log: &mut Messages) -> Option<i32> {
+    let color = if target.is_player() { colors::ORANGE } else { colors::WHITE };
+    let defender = target.name_in_messages();
+    let taken = target.fighter.map_or(0, |f| f.damage_taken(amount, damage_type));
+    if taken > 0 {
+        log.add(format!("The {} hits {} for {} hit points.", source, defender, taken), color);
+        target.take_damage(amount, damage_type, log)
+    } else {
+        log.add(format!("The {} hits {} but it has no effect!", source, defender), color);
+        None
+    }
+}
+
+/// Heal the target by the given amount.
+/// Returns false if it was already at full health.
+pub fn heal(target: &mut Element, amount: i32, log: &mut Messages) -> bool {
+    match target.fighter {
+        Some(fighter) if fighter.hp < fighter.max_hp => {}
+        _ => return false,
+    }
+    target.heal(amount);
+    if target.is_player() {
+        log.add("Your wounds start to feel better!", colors::LIGHT_VIOLET);
+    } else {
+        log.add(format!("{} looks healthier.", capitalize(&target.name_in_messages())),
+                colors::LIGHT_VIOLET);
+    }
+    true
+}
+
+/// Give the player the experience of an enemy killed by no one's blow,
+/// such as by poison or lava, as they most likely brought it about.
+pub fn reward_player(elements: &mut [Element], victim_id: usize, xp: Option<i32>, log: &mut Messages) {
+    if let Some(xp) = xp {
+        if Faction::Player.is_hostile_to(elements[victim_id].faction) {
+            elements[PLAYER].gain_xp(xp, log);
+        }
+    }
+}
@@ -1,9 +1,39 @@
 use crate::{
+    ability::{Ability, KnownAbility},
     colors::*,
-    constants::PLAYER,
+    faction::Faction,
+    messages::{Messages, MessageLog},
+    effects,
+    constants::{
+        ATTACK_DIE,
+        BASE_EVASION,
+        BLIND_TURNS,
+        CONFUSE_RANGE,
+        CONFUSE_TURNS,
+        GRAZE_MARGIN,
+        HASTE_TURNS,
+        INVISIBILITY_TURNS,
+        NORMAL_SPEED,
+        PLAYER,
+        POISON_TURNS,
+        REGENERATION_POTENCY,
+        REGENERATION_TURNS,
+        STUN_TURNS,
+        TORCH_RADIUS,
+        TURN_ENERGY,
+        USE_ITEM_COST,
+    },
+    damage::{DamageType, Resistance, Resistances},
+    dice::Dice,
+    light::Light,
+    map::Map,
     position::Position,
+    status::{self, Status, StatusKind},
+    utils::GameRng,
 };
-use tcod::colors::{Color};
+use rand::Rng;
+use std::cmp;
+use tcod::colors::{self, Color};
 
 #[derive(Debug)]
 pub struct Element {
@@ -16,11 +46,39 @@ pub struct Element {
     pub fighter: Option<Fighter>,
     pub ai: Option<Ai>,
     pub item: Option<Item>,
+    pub light: Option<Light>,
+    /// how far the element can notice others, when they are lit
+    pub sight_radius: i32,
+    /// makes the element harder to notice
+    pub stealth: i32,
+    /// where a monster last saw the player, if it is still after them
+    pub last_seen: Option<LastSeen>,
+    pub faction: Faction,
+    /// the pack a monster was spawned with, if any
+    pub pack: Option<PackMember>,
+    /// what a monster can do besides walking and attacking
+    pub abilities: Vec<KnownAbility>,
+    /// lasting effects, see `status`
+    pub statuses: Vec<Status>,
+    /// the player's experience level, starting at 1
+    pub level: i32,
+    /// experience the player gathered towards their next level
+    pub xp: i32,
+    /// energy gained each turn, see `scheduler`
+    pub speed: i32,
+    /// the element can act once it has `TURN_ENERGY`, actions spend it
+    pub energy: i32,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Item {
     Heal,
+    Haste,
+    Regeneration,
+    Invisibility,
+    Confusion,
+    Blinding,
+    Thunder,
 }
 
 impl Element {
@@ -31,13 +89,25 @@ impl Element {
         Element {
             position: Position::new(x, y),
             display_name: display_name.to_owned(),
-            char: char,
-            color: color,
-            block_movement: block_movement,
+            char,
+            color,
+            block_movement,
             alive: false,
             fighter: None,
             ai: None,
             item: None,
+            light: None,
+            sight_radius: 0,
+            stealth: 0,
+            last_seen: None,
+            faction: Faction::Neutral,
+            pack: None,
+            abilities: vec![],
+            statuses: vec![],
+            level: 1,
+            xp: 0,
+            speed: NORMAL_SPEED,
+            energy: 0,
         }
     }
 
@@ -54,8 +124,25 @@ impl Element {
         self.position.distance_to(&other.position)
     }
 
-    pub fn take_damage(&mut self, damage: i32) {
+    pub fn is_player(&self) -> bool {
+        self.fighter.is_some_and(|f| f.on_death == DeathCallback::Player)
+    }
+
+    /// how the element is named in messages: "you" for the player,
+    /// "the orc" for others
+    pub fn name_in_messages(&self) -> String {
+        if self.is_player() {
+            "you".into()
+        } else {
+            format!("the {}", self.display_name)
+        }
+    }
+
+    /// Take damage of the given type, lessened by armour and resistances.
+    /// Returns the experience the element is worth if it died.
+    pub fn take_damage(&mut self, damage: i32, damage_type: DamageType, log: &mut Messages) -> Option<i32> {
         if let Some(fighter) = self.fighter.as_mut() {
+            let damage = fighter.damage_taken(damage, damage_type);
             if damage > 0 {
                 fighter.hp -= damage;
             }
@@ -63,19 +150,89 @@ impl Element {
         if let Some(fighter) = self.fighter {
             if fighter.hp <= 0 {
                 self.alive = false;
-                fighter.on_death.callback(self);
+                fighter.on_death.callback(self, log);
+                return Some(fighter.xp_value);
             }
         }
+        None
+    }
+
+    /// Earn experience from a kill; only the player levels up, so others
+    /// gain nothing.
+    pub fn gain_xp(&mut self, xp: i32, log: &mut Messages) {
+        if self.is_player() && xp > 0 {
+            self.xp += xp;
+            log.add(format!("You gain {} experience points.", xp), colors::LIGHT_YELLOW);
+        }
     }
 
-    pub fn attack(&mut self, target: &mut Element) {
-        let damage = self.fighter.map_or(0, |f| f.strength) -
-            target.fighter.map_or(0, |f| f.defense);
+    /// Roll an attack against the target's evasion: close misses still
+    /// graze it for half the damage, and a natural 20 is a critical hit
+    /// rolling the damage twice.
+    pub fn attack(&mut self, target: &mut Element, rng: &mut GameRng, log: &mut Messages) {
+        let attacker_fighter = match self.fighter {
+            Some(fighter) => fighter,
+            None => return,
+        };
+        let hit = self.roll_hit(target, rng);
+        let rolled = roll_damage(attacker_fighter.damage, hit, rng);
+        let damage_type = attacker_fighter.damage_type;
+        let damage = target.fighter.map_or(0, |f| f.damage_taken(rolled, damage_type));
+
+        let player = self.is_player();
+        let conjugate = |verb: &str, third_person: &str| -> String {
+            if player { verb.into() } else { third_person.into() }
+        };
+        let color = if target.is_player() { colors::ORANGE } else { colors::WHITE };
+        let attacker = capitalize(&self.name_in_messages());
+        let defender = target.name_in_messages();
+        let (verb, end) = match hit {
+            Hit::Miss => {
+                let miss = conjugate("miss", "misses");
+                log.add(format!("{} {} {} but {}.", attacker, conjugate("attack", "attacks"), defender, miss),
+                        color);
+                return;
+            }
+            Hit::Graze => (conjugate("graze", "grazes"), "."),
+            Hit::Normal => (conjugate("attack", "attacks"), "."),
+            Hit::Critical => (format!("critically {}", conjugate("hit", "hits")), "!"),
+        };
         if damage > 0 {
-            println!("{} attacks {} for {} hit points", self.display_name, target.display_name, damage);
-            target.take_damage(damage);
+            log.add(format!("{} {} {} for {} hit points{}", attacker, verb, defender, damage, end), color);
+            if let Some(xp) = target.take_damage(rolled, damage_type, log) {
+                self.gain_xp(xp, log);
+            }
+            if damage_type == DamageType::Poison && target.alive {
+                status::afflict(target, StatusKind::Poisoned, POISON_TURNS, 1, log);
+            }
         } else {
-            println!("{} attacks {} but it has no effect!", self.display_name, target.display_name);
+            log.add(format!("{} {} {} but it has no effect!", attacker, verb, defender), color);
+        }
+    }
+
+    /// Roll a ranged attack against the target's evasion, like in melee.
+    /// Returns the damage rolled, or None if it missed.
+    pub fn roll_ranged_damage(&self, target: &Element, rng: &mut GameRng) -> Option<i32> {
+        let dice = self.fighter?.damage;
+        match self.roll_hit(target, rng) {
+            Hit::Miss => None,
+            hit => Some(roll_damage(dice, hit, rng)),
+        }
+    }
+
+    fn roll_hit(&self, target: &Element, rng: &mut GameRng) -> Hit {
+        let accuracy = self.fighter.map_or(0, |f| f.accuracy);
+        let evasion = target.fighter.map_or(0, |f| f.evasion);
+        let roll = rng.gen_range(1, ATTACK_DIE + 1);
+        let margin = roll + accuracy - (BASE_EVASION + evasion);
+        if roll == ATTACK_DIE {
+            Hit::Critical
+        } else if roll == 1 || margin < -GRAZE_MARGIN {
+            Hit::Miss
+        } else if margin < 0 {
+            Hit::Graze
+        } else {
+            Hit::Normal
         }
     }
 
@@ -90,15 +247,56 @@ impl Element {
     }
 }
 
+/// damage of an attack, half for grazes and rolled twice for critical hits
+fn roll_damage(dice: Dice, hit: Hit, rng: &mut GameRng) -> i32 {
+    match hit {
+        Hit::Miss => 0,
+        Hit::Graze => dice.roll(rng) / 2,
+        Hit::Normal => dice.roll(rng),
+        Hit::Critical => dice.roll(rng) + dice.roll(rng),
+    }
+}
+
+/// how well an attack roll went
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Hit {
+    Miss,
+    Graze,
+    Normal,
+    Critical,
+}
+
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Fighter {
     pub max_hp: i32,
     pub hp: i32,
+    /// subtracted from the damage taken
     pub defense: i32,
-    pub strength: i32,
+    pub damage: Dice,
+    pub damage_type: DamageType,
+    /// added to attack rolls
+    pub accuracy: i32,
+    /// makes attacks against the element miss more often
+    pub evasion: i32,
+    pub resistances: Resistances,
+    /// experience killing the element is worth
+    pub xp_value: i32,
     on_death: DeathCallback,
 }
 
+impl Fighter {
+    /// what is left of the given damage once through armour, which only
+    /// stops physical damage, and resistances
+    pub fn damage_taken(&self, damage: i32, damage_type: DamageType) -> i32 {
+        let damage = if damage_type == DamageType::Physical {
+            damage - self.defense
+        } else {
+            damage
+        };
+        cmp::max(0, self.resistances.of(damage_type).apply(damage))
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq)]
 enum DeathCallback {
     Player,
@@ -106,29 +304,39 @@ enum DeathCallback {
 }
 
 impl DeathCallback {
-    fn callback(self, element: &mut Element) {
+    fn callback(self, element: &mut Element, log: &mut Messages) {
         use DeathCallback::*;
-        let callback: fn(&mut Element) = match self {
+        let callback: fn(&mut Element, &mut Messages) = match self {
             Player => player_death,
             Monster => monster_death,
         };
-        callback(element);
+        callback(element, log);
     }
 }
 
-fn player_death(player: &mut Element) {
-    println!("You died!");
+/// the text with its first letter in upper case
+pub fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+fn player_death(player: &mut Element, log: &mut Messages) {
+    log.add("You died!", colors::RED);
     player.char = '%';
     player.color = COLOR_PLAYER_DEAD;
 }
 
-fn monster_death(monster: &mut Element) {
-    println!("{} is dead!", monster.display_name);
+fn monster_death(monster: &mut Element, log: &mut Messages) {
+    log.add(format!("{} is dead!", capitalize(&monster.name_in_messages())), colors::ORANGE);
     monster.char = '%';
     monster.color = COLOR_MONSTER_DEAD;
     monster.block_movement = false;
     monster.fighter = None;
     monster.ai = None;
+    monster.light = None;
     monster.display_name = format!("remains of {}", monster.display_name);
 }
 
@@ -138,10 +346,17 @@ pub fn make_orc(x: i32, y: i32) -> Element {
         max_hp: 10,
         hp: 10,
         defense: 0,
-        strength: 3,
+        damage: Dice::new(1, 4, 1),
+        damage_type: DamageType::Physical,
+        accuracy: 2,
+        evasion: 0,
+        resistances: Resistances::none(),
+        xp_value: 35,
         on_death: DeathCallback::Monster,
     });
-    orc.ai = Some(Ai);
+    orc.ai = Some(Ai::Wandering);
+    orc.sight_radius = 8;
+    orc.faction = Faction::Orcs;
     orc.alive = true;
     orc
 }
@@ -152,22 +367,215 @@ pub fn make_troll(x: i32, y: i32) -> Element {
         max_hp: 10,
         hp: 10,
         defense:1,
-        strength: 4,
+        damage: Dice::new(1, 6, 1),
+        damage_type: DamageType::Physical,
+        accuracy: 2,
+        evasion: 0,
+        resistances: Resistances::none()
+            .with(DamageType::Fire, Resistance::Vulnerable),
+        xp_value: 100,
         on_death: DeathCallback::Monster,
     });
-    troll.ai = Some(Ai);
+    troll.ai = Some(Ai::Sleeping);
+    troll.sight_radius = 6;
+    troll.speed = NORMAL_SPEED / 2;
+    troll.faction = Faction::Trolls;
     troll.alive = true;
     troll
 }
 
+pub fn make_goblin(x: i32, y: i32) -> Element {
+    let mut goblin = Element::new(x, y, "goblin", 'g', COLOR_MONSTER_GOBLIN, true);
+    goblin.fighter = Some(Fighter{
+        max_hp: 6,
+        hp: 6,
+        defense: 0,
+        damage: Dice::new(1, 3, 0),
+        damage_type: DamageType::Physical,
+        accuracy: 3,
+        evasion: 4,
+        resistances: Resistances::none(),
+        xp_value: 20,
+        on_death: DeathCallback::Monster,
+    });
+    goblin.ai = Some(Ai::Cowardly { flee_below: 0.5 });
+    goblin.abilities = vec![KnownAbility::new(Ability::Blink { range: 5 }, 10)];
+    goblin.sight_radius = 10;
+    goblin.speed = 2 * NORMAL_SPEED;
+    goblin.faction = Faction::Orcs;
+    goblin.alive = true;
+    goblin
+}
+
+pub fn make_orc_archer(x: i32, y: i32) -> Element {
+    let mut archer = Element::new(x, y, "orc archer", 'o', COLOR_MONSTER_ORC_ARCHER, true);
+    archer.fighter = Some(Fighter{
+        max_hp: 8,
+        hp: 8,
+        defense: 0,
+        damage: Dice::new(1, 4, 0),
+        damage_type: DamageType::Physical,
+        accuracy: 4,
+        evasion: 1,
+        resistances: Resistances::none(),
+        xp_value: 40,
+        on_death: DeathCallback::Monster,
+    });
+    archer.ai = Some(Ai::Ranged { range: 6, projectile: "arrow" });
+    archer.sight_radius = 10;
+    archer.faction = Faction::Orcs;
+    archer.alive = true;
+    archer
+}
+
+pub fn make_orc_shaman(x: i32, y: i32) -> Element {
+    let mut shaman = Element::new(x, y, "orc shaman", 'o', COLOR_MONSTER_ORC_SHAMAN, true);
+    shaman.fighter = Some(Fighter{
+        max_hp: 8,
+        hp: 8,
+        defense: 0,
+        damage: Dice::new(1, 2, 0),
+        damage_type: DamageType::Physical,
+        accuracy: 1,
+        evasion: 1,
+        resistances: Resistances::none()
+            .with(DamageType::Lightning, Resistance::Resistant),
+        xp_value: 60,
+        on_death: DeathCallback::Monster,
+    });
+    shaman.ai = Some(Ai::Ranged { range: 5, projectile: "stone" });
+    shaman.abilities = vec![
+        KnownAbility::new(Ability::HealAlly { amount: 6, range: 6 }, 5),
+        KnownAbility::new(Ability::Hex { status: StatusKind::Slowed, turns: 5, range: 5 }, 8),
+        KnownAbility::new(Ability::Bolt {
+            name: "lightning bolt",
+            damage: 5,
+            damage_type: DamageType::Lightning,
+            range: 5,
+        }, 3),
+    ];
+    shaman.sight_radius = 10;
+    shaman.faction = Faction::Orcs;
+    shaman.alive = true;
+    shaman
+}
+
+pub fn make_orc_warlord(x: i32, y: i32) -> Element {
+    let mut warlord = Element::new(x, y, "orc warlord", 'O', COLOR_MONSTER_ORC_WARLORD, true);
+    warlord.fighter = Some(Fighter{
+        max_hp: 16,
+        hp: 16,
+        defense: 1,
+        damage: Dice::new(1, 6, 1),
+        damage_type: DamageType::Physical,
+        accuracy: 4,
+        evasion: 1,
+        resistances: Resistances::none(),
+        xp_value: 120,
+        on_death: DeathCallback::Monster,
+    });
+    warlord.ai = Some(Ai::Wandering);
+    warlord.abilities = vec![KnownAbility::new(Ability::Summon { make: make_orc, count: 2 }, 20)];
+    warlord.sight_radius = 8;
+    warlord.faction = Faction::Orcs;
+    warlord.alive = true;
+    warlord
+}
+
+pub fn make_drake(x: i32, y: i32) -> Element {
+    let mut drake = Element::new(x, y, "cave drake", 'D', COLOR_MONSTER_DRAKE, true);
+    drake.fighter = Some(Fighter{
+        max_hp: 14,
+        hp: 14,
+        defense: 1,
+        damage: Dice::new(1, 4, 1),
+        damage_type: DamageType::Fire,
+        accuracy: 3,
+        evasion: 1,
+        resistances: Resistances::none()
+            .with(DamageType::Fire, Resistance::Immune)
+            .with(DamageType::Cold, Resistance::Vulnerable),
+        xp_value: 150,
+        on_death: DeathCallback::Monster,
+    });
+    drake.ai = Some(Ai::Sleeping);
+    drake.abilities = vec![KnownAbility::new(Ability::Breath {
+        name: "fire",
+        damage: 6,
+        damage_type: DamageType::Fire,
+        range: 4,
+    }, 5)];
+    drake.sight_radius = 8;
+    drake.faction = Faction::Trolls;
+    drake.alive = true;
+    drake
+}
+
+pub fn make_rat(x: i32, y: i32) -> Element {
+    let mut rat = Element::new(x, y, "cave rat", 'r', COLOR_MONSTER_RAT, true);
+    rat.fighter = Some(Fighter{
+        max_hp: 3,
+        hp: 3,
+        defense: 0,
+        damage: Dice::new(1, 2, 0),
+        damage_type: DamageType::Poison,
+        accuracy: 1,
+        evasion: 3,
+        resistances: Resistances::none()
+            .with(DamageType::Poison, Resistance::Immune),
+        xp_value: 5,
+        on_death: DeathCallback::Monster,
+    });
+    rat.ai = Some(Ai::Wandering);
+    rat.sight_radius = 4;
+    rat.alive = true;
+    rat
+}
+
+pub fn make_dog(x: i32, y: i32) -> Element {
+    let mut dog = Element::new(x, y, "dog", 'd', COLOR_ALLY_DOG, true);
+    dog.fighter = Some(Fighter{
+        max_hp: 12,
+        hp: 12,
+        defense: 0,
+        damage: Dice::new(1, 4, 1),
+        damage_type: DamageType::Physical,
+        accuracy: 3,
+        evasion: 2,
+        resistances: Resistances::none(),
+        xp_value: 0,
+        on_death: DeathCallback::Monster,
+    });
+    dog.ai = Some(Ai::Follower);
+    dog.sight_radius = 10;
+    dog.faction = Faction::Player;
+    dog.alive = true;
+    dog
+}
+
+/// the light the player carries, unless they douse it
+pub fn make_torch() -> Light {
+    Light { radius: TORCH_RADIUS, color: COLOR_TORCH }
+}
+
 pub fn make_player(x: i32, y: i32) -> Element {
     let mut player = Element::new(x, y, "player", '@', COLOR_PLAYER, true);
     player.alive = true;
+    player.faction = Faction::Player;
+    player.light = Some(make_torch());
+    player.stealth = 3;
+    // the player acts first
+    player.energy = TURN_ENERGY;
     player.fighter = Some(Fighter{
         max_hp: 30,
         hp: 30,
         defense: 2,
-        strength: 5,
+        damage: Dice::new(1, 6, 2),
+        damage_type: DamageType::Physical,
+        accuracy: 4,
+        evasion: 2,
+        resistances: Resistances::none(),
+        xp_value: 0,
         on_death: DeathCallback::Player,
     });
     player
@@ -203,48 +611,313 @@ pub fn drop_item(inventory_id: usize,
     elements.push(item);
 }
 
+/// Use an item of the inventory.
+/// Returns true if it was used, spending the player's time.
 pub fn use_item(inventory_id: usize,
                 inventory: &mut Vec<Element>,
-                element: &mut [Element]) {
+                element: &mut [Element],
+                map: &Map,
+                log: &mut Messages) -> bool {
     use Item::*;
     // just call the "use_function" if it is defined
     if let Some(item) = inventory[inventory_id].item {
         let on_use = match item {
             Heal => cast_heal,
+            Haste => cast_haste,
+            Regeneration => cast_regeneration,
+            Invisibility => cast_invisibility,
+            Confusion => cast_confusion,
+            Blinding => cast_blinding,
+            Thunder => cast_thunder,
         };
-        match on_use(inventory_id, element) {
+        match on_use(inventory_id, element, map, log) {
             UseResult::UsedUp => {
                 // destroy after use, unless it was cancelled for some reason
                 inventory.remove(inventory_id);
+                element[PLAYER].energy -= USE_ITEM_COST;
+                true
             }
             UseResult::Cancelled => {
                 // message(messages, "Cancelled", colors::WHITE);
+                false
             }
         }
     } else {
         // message(messages,
         //         format!("The {} cannot be used.", inventory[inventory_id].name),
         //         colors::WHITE);
+        false
     }
 }
 
-fn cast_heal(_inventory_id: usize, elements: &mut [Element]) -> UseResult {
+fn cast_heal(_inventory_id: usize, elements: &mut [Element], _map: &Map, log: &mut Messages) -> UseResult {
     // heal the player
-    if let Some(fighter) = elements[PLAYER].fighter {
-        if fighter.hp == fighter.max_hp {
-            return UseResult::Cancelled;
-        }
-        elements[PLAYER].heal(5);
+    if effects::heal(&mut elements[PLAYER], 5, log) {
         return UseResult::UsedUp;
     }
+    log.add("You are already at full health.", colors::RED);
     UseResult::Cancelled
 }
 
+fn cast_haste(_inventory_id: usize, elements: &mut [Element], _map: &Map, log: &mut Messages) -> UseResult {
+    status::afflict(&mut elements[PLAYER], StatusKind::Hasted, HASTE_TURNS, 0, log);
+    UseResult::UsedUp
+}
+
+fn cast_regeneration(_inventory_id: usize, elements: &mut [Element], _map: &Map, log: &mut Messages) -> UseResult {
+    status::afflict(&mut elements[PLAYER],
+                    StatusKind::Regenerating,
+                    REGENERATION_TURNS,
+                    REGENERATION_POTENCY,
+                    log);
+    UseResult::UsedUp
+}
+
+fn cast_invisibility(_inventory_id: usize, elements: &mut [Element], _map: &Map, log: &mut Messages) -> UseResult {
+    status::afflict(&mut elements[PLAYER], StatusKind::Invisible, INVISIBILITY_TURNS, 0, log);
+    UseResult::UsedUp
+}
+
+/// living monsters hostile to the player, which they can see
+fn enemies_in_sight<'a>(elements: &'a [Element], map: &'a Map) -> impl Iterator<Item = usize> + 'a {
+    (0..elements.len()).filter(move |&id| {
+        let element = &elements[id];
+        element.alive &&
+            element.ai.is_some() &&
+            Faction::Player.is_hostile_to(element.faction) &&
+            map.is_in_sight(element.position.x, element.position.y) &&
+            status::is_seen_by(element, &elements[PLAYER])
+    })
+}
+
+fn cast_confusion(_inventory_id: usize, elements: &mut [Element], map: &Map, log: &mut Messages) -> UseResult {
+    // confuse the nearest enemy in range
+    let target_id = enemies_in_sight(elements, map)
+        .map(|id| (id, elements[id].distance_to(&elements[PLAYER])))
+        .filter(|&(_, distance)| distance <= CONFUSE_RANGE)
+        .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())
+        .map(|(id, _)| id);
+    match target_id {
+        Some(target_id) => {
+            status::afflict(&mut elements[target_id], StatusKind::Confused, CONFUSE_TURNS, 0, log);
+            UseResult::UsedUp
+        }
+        None => {
+            log.add("No enemy is close enough to confuse.", colors::RED);
+            UseResult::Cancelled
+        }
+    }
+}
+
+fn cast_blinding(_inventory_id: usize, elements: &mut [Element], map: &Map, log: &mut Messages) -> UseResult {
+    // blind every enemy in sight
+    let targets: Vec<_> = enemies_in_sight(elements, map).collect();
+    if targets.is_empty() {
+        log.add("There is no enemy in sight to blind.", colors::RED);
+        return UseResult::Cancelled;
+    }
+    log.add("The scroll bursts into a blinding light!", colors::WHITE);
+    for id in targets {
+        status::afflict(&mut elements[id], StatusKind::Blinded, BLIND_TURNS, 0, log);
+    }
+    UseResult::UsedUp
+}
+
+fn cast_thunder(_inventory_id: usize, elements: &mut [Element], map: &Map, log: &mut Messages) -> UseResult {
+    // stun every enemy next to the player
+    let targets: Vec<_> = enemies_in_sight(elements, map)
+        .filter(|&id| elements[id].distance_to(&elements[PLAYER]) < 2.0)
+        .collect();
+    if targets.is_empty() {
+        log.add("There is no enemy next to you to stun.", colors::RED);
+        return UseResult::Cancelled;
+    }
+    log.add("A clap of thunder shakes the dungeon!", colors::WHITE);
+    for id in targets {
+        status::afflict(&mut elements[id], StatusKind::Stunned, STUN_TURNS, 0, log);
+    }
+    UseResult::UsedUp
+}
+
 pub fn make_potion(x: i32, y: i32) -> Element {
-    let mut potion = Element::new(x, y, "potion", '!', COLOR_POTION, false);
+    let mut potion = Element::new(x, y, "potion of healing", '!', COLOR_POTION, false);
     potion.item = Some(Item::Heal);
     potion
 }
 
+pub fn make_potion_of_haste(x: i32, y: i32) -> Element {
+    let mut potion = Element::new(x, y, "potion of haste", '!', COLOR_POTION, false);
+    potion.item = Some(Item::Haste);
+    potion
+}
+
+pub fn make_potion_of_regeneration(x: i32, y: i32) -> Element {
+    let mut potion = Element::new(x, y, "potion of regeneration", '!', COLOR_POTION, false);
+    potion.item = Some(Item::Regeneration);
+    potion
+}
+
+pub fn make_potion_of_invisibility(x: i32, y: i32) -> Element {
+    let mut potion = Element::new(x, y, "potion of invisibility", '!', COLOR_POTION, false);
+    potion.item = Some(Item::Invisibility);
+    potion
+}
+
+pub fn make_scroll_of_confusion(x: i32, y: i32) -> Element {
+    let mut scroll = Element::new(x, y, "scroll of confusion", '?', COLOR_SCROLL, false);
+    scroll.item = Some(Item::Confusion);
+    scroll
+}
+
+pub fn make_scroll_of_blinding(x: i32, y: i32) -> Element {
+    let mut scroll = Element::new(x, y, "scroll of blinding", '?', COLOR_SCROLL, false);
+    scroll.item = Some(Item::Blinding);
+    scroll
+}
+
+pub fn make_scroll_of_thunder(x: i32, y: i32) -> Element {
+    let mut scroll = Element::new(x, y, "scroll of thunder", '?', COLOR_SCROLL, false);
+    scroll.item = Some(Item::Thunder);
+    scroll
+}
+
+/// Where a monster last saw the player, and how long it keeps looking
+/// around there before giving up
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LastSeen {
+    pub x: i32,
+    pub y: i32,
+    /// true once the monster reached the spot, or could not get there
+    pub searching: bool,
+    pub search_turns: i32,
+}
+
+/// Monsters spawned together share a target, and run away once their
+/// leader is dead
 #[derive(Clone, Copy, Debug, PartialEq)]
-pub struct Ai;
+pub struct PackMember {
+    pub pack: u32,
+    pub leader: bool,
+}
+
+/// How a monster behaves on its turn
+#[derive(Clone, Debug, PartialEq)]
+pub enum Ai {
+    /// goes for the player and attacks in melee once it notices them
+    Basic,
+    /// walks around at random until it notices the player
+    Wandering,
+    /// does nothing until it notices the player, then wakes up as Basic
+    Sleeping,
+    /// fights like Basic, but runs away once its HP falls below the given
+    /// fraction of its maximum
+    Cowardly { flee_below: f32 },
+    /// keeps its distance and shoots the projectile from up to `range`
+    /// tiles away
+    Ranged { range: i32, projectile: &'static str },
+    /// stays close to the player, fighting the enemies it notices around them
+    Follower,
+    /// runs away from its enemies for some turns after losing its pack
+    /// leader, then goes back to what it was doing
+    Routed { previous: Box<Ai>, turns: i32 },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::terrain::Terrain;
+    use crate::utils::seeded_rng;
+
+    /// the messages of many attacks from the player on a sturdy orc
+    fn attack_messages(seed: usize) -> Vec<String> {
+        let mut rng = seeded_rng(seed);
+        let mut player = make_player(0, 0);
+        let mut orc = make_orc(1, 0);
+        let mut log = vec![];
+        let mut messages = vec![];
+        for _ in 0..200 {
+            orc.fighter.as_mut().unwrap().hp = 1000;
+            player.attack(&mut orc, &mut rng, &mut log);
+            messages.extend(log.drain(..).map(|(message, _)| message));
+        }
+        messages
+    }
+
+    #[test]
+    fn attacks_miss_graze_hit_and_crit() {
+        let messages = attack_messages(0);
+        let has = |start: &str| messages.iter().any(|message| message.starts_with(start));
+        assert!(has("You attack the orc but miss."));
+        assert!(has("You graze the orc for "));
+        assert!(has("You attack the orc for "));
+        assert!(has("You critically hit the orc for "));
+        assert!(messages.iter()
+            .filter(|message| message.starts_with("You critically hit"))
+            .all(|message| message.ends_with("hit points!")));
+    }
+
+    #[test]
+    fn fights_replay_with_the_same_seed() {
+        assert_eq!(attack_messages(7), attack_messages(7));
+    }
+
+    #[test]
+    fn killers_gain_the_experience_of_their_victims() {
+        let mut rng = seeded_rng(0);
+        let mut player = make_player(0, 0);
+        let mut orc = make_orc(1, 0);
+        let mut log = vec![];
+        while orc.alive {
+            player.attack(&mut orc, &mut rng, &mut log);
+        }
+        assert_eq!(player.xp, 35);
+        assert_eq!(log.last().map(|(message, _)| message.as_str()),
+                   Some("You gain 35 experience points."));
+    }
+
+    #[test]
+    fn potions_and_scrolls_put_statuses_on() {
+        // the player lights a corridor with an orc two steps away
+        let mut map = Map::new(6, 3);
+        for x in 1..5 {
+            map.set_terrain(x, 1, Terrain::Floor);
+        }
+        map.update_fov(1, 1, TORCH_RADIUS, &[(1, 1, make_torch())]);
+        let elements = &mut [make_player(1, 1), make_orc(3, 1)];
+        let mut log = vec![];
+
+        let mut inventory = vec![make_potion_of_haste(0, 0), make_scroll_of_confusion(0, 0)];
+        assert!(use_item(0, &mut inventory, elements, &map, &mut log));
+        assert!(use_item(0, &mut inventory, elements, &map, &mut log));
+        assert!(status::has(&elements[PLAYER], StatusKind::Hasted));
+        assert!(status::has(&elements[1], StatusKind::Confused));
+        assert!(inventory.is_empty());
+
+        // thunder only stuns enemies right next to the player
+        let mut inventory = vec![make_scroll_of_thunder(0, 0)];
+        assert!(!use_item(0, &mut inventory, elements, &map, &mut log));
+        assert_eq!(inventory.len(), 1);
+    }
+
+    #[test]
+    fn torches_go_out_with_their_bearers() {
+        let mut orc = make_orc(0, 0);
+        orc.light = Some(make_torch());
+        orc.take_damage(1000, DamageType::Physical, &mut vec![]);
+        assert!(!orc.alive);
+        assert_eq!(orc.light, None);
+    }
+
+    #[test]
+    fn monsters_killing_each_other_gain_nothing() {
+        let mut rng = seeded_rng(0);
+        let mut orc = make_orc(0, 0);
+        let mut rat = make_rat(1, 0);
+        let mut log = vec![];
+        while rat.alive {
+            orc.attack(&mut rat, &mut rng, &mut log);
+        }
+        assert_eq!(orc.xp, 0);
+        assert_eq!(orc.fighter.map(|f| f.xp_value), Some(35));
+    }
+}
@@ -0,0 +1,22 @@
+/// Sides elements fight for
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Faction {
+    Player,
+    Orcs,
+    Trolls,
+    /// creatures going about their business, hostile to nobody
+    Neutral,
+}
+
+/// pairs of factions fighting each other, both ways
+const HOSTILITIES : [(Faction, Faction); 3] = [
+    (Faction::Player, Faction::Orcs),
+    (Faction::Player, Faction::Trolls),
+    (Faction::Orcs, Faction::Trolls),
+];
+
+impl Faction {
+    pub fn is_hostile_to(self, other: Faction) -> bool {
+        HOSTILITIES.iter().any(|&pair| pair == (self, other) || pair == (other, self))
+    }
+}
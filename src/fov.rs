@@ -5,45 +5,183 @@ const FOV_MULT : [[i32; 8]; 4] = [
     [1, 0, 0, 1, -1, 0, 0, -1]
 ];
 
-#[derive(Debug)]
+/// The different ways a FovMap can compute what is in sight
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FovAlgorithm {
+    /// Recursive shadowcasting. Fast, but not symmetric: a cell seen from
+    /// another does not always see it back.
+    Shadowcasting,
+    /// Shadowcasting with exact slopes, where every floor cell seen from
+    /// another also sees it back.
+    SymmetricShadowcasting,
+    /// A cell is seen if any straight line going from around the viewer to
+    /// around the cell is clear, revealing more around pillars and corners.
+    Permissive,
+    /// Cast a straight ray towards each cell at the edge of the radius.
+    Raycasting,
+}
+
+/// Offsets, from a cell's center, of the points lines are cast from or to
+/// with `FovAlgorithm::Permissive`.
+const PERMISSIVE_POINTS : [(f32, f32); 5] = [
+    (0.0, 0.0), (-0.4, -0.4), (0.4, -0.4), (-0.4, 0.4), (0.4, 0.4),
+];
+
+#[derive(Clone, Copy, Debug)]
+struct FovCell {
+    obstacle: bool,
+    in_sight: bool,
+    /// was seen at least once, see `explore`
+    explored: bool,
+}
+
+#[derive(Clone, Debug)]
 pub struct FovMap {
-    map : Vec<Vec<(bool, bool)>>,
+    map : Vec<Vec<FovCell>>,
+    /// cells currently in sight, so they can be reset without going through
+    /// the whole map
+    visible: Vec<(i32, i32)>,
+    /// position and radius of the last computation, if still valid
+    last_origin: Option<(i32, i32, i32)>,
+    algorithm: FovAlgorithm,
 }
 
 impl FovMap {
     pub fn new(width: i32, height: i32) -> Self {
+        let cell = FovCell { obstacle: false, in_sight: false, explored: false };
         FovMap {
-            map: vec![vec![(false, false); height as usize]; width as usize],
+            map: vec![vec![cell; height as usize]; width as usize],
+            visible: vec![],
+            last_origin: None,
+            algorithm: FovAlgorithm::Shadowcasting,
         }
     }
 
+    pub fn set_algorithm(&mut self, algorithm: FovAlgorithm) {
+        self.algorithm = algorithm;
+        self.last_origin = None;
+    }
+
+    pub fn algorithm(&self) -> FovAlgorithm {
+        self.algorithm
+    }
+
     pub fn set(&mut self, x : i32, y: i32, val : bool) {
-        self.map[x as usize][y as usize].0 = val;
+        let cell = &mut self.map[x as usize][y as usize];
+        if cell.obstacle != val {
+            cell.obstacle = val;
+            self.last_origin = None;
+        }
+    }
+
+    /// take the obstacles and algorithm of another map of the same size
+    pub fn copy_obstacles(&mut self, other: &FovMap) {
+        for (column, other_column) in self.map.iter_mut().zip(&other.map) {
+            for (cell, other_cell) in column.iter_mut().zip(other_column) {
+                cell.obstacle = other_cell.obstacle;
+            }
+        }
+        self.algorithm = other.algorithm;
+        self.last_origin = None;
     }
 
     pub fn is_obstacle(&self, x: i32, y: i32) -> bool {
-        self.map[x as usize][y as usize].0
+        self.map[x as usize][y as usize].obstacle
     }
 
     pub fn is_in_sight(&self, x: i32, y: i32) -> bool {
-        self.map[x as usize][y as usize].1
+        self.map[x as usize][y as usize].in_sight
+    }
+
+    pub fn is_explored(&self, x: i32, y: i32) -> bool {
+        self.map[x as usize][y as usize].explored
+    }
+
+    pub fn visible_cells(&self) -> &[(i32, i32)] {
+        &self.visible
+    }
+
+    pub fn explore(&mut self, x: i32, y: i32) {
+        self.map[x as usize][y as usize].explored = true;
+    }
+
+    fn reveal(&mut self, x: i32, y: i32) {
+        let cell = &mut self.map[x as usize][y as usize];
+        if !cell.in_sight {
+            cell.in_sight = true;
+            self.visible.push((x, y));
+        }
+    }
+
+    /// Compute the FOV, unless neither the origin nor the obstacles changed
+    /// since the last computation.
+    /// Returns true if it was recomputed.
+    pub fn update_fov(&mut self, x: i32, y: i32, radius: i32) -> bool {
+        if self.last_origin == Some((x, y, radius)) {
+            return false;
+        }
+        self.compute_fov(x, y, radius);
+        true
     }
 
     pub fn compute_fov(&mut self, x: i32, y: i32, radius: i32) {
         self.reset();
-        for i in 0..8 {
-            self.cast_light(x, y, radius, 1, 1.0, 0.0,
-                            FOV_MULT[0][i], FOV_MULT[1][i],
-                            FOV_MULT[2][i], FOV_MULT[3][i]);
+        match self.algorithm {
+            FovAlgorithm::Shadowcasting => {
+                // one octant per column of FOV_MULT
+                let [xxs, xys, yxs, yys] = &FOV_MULT;
+                let octants = xxs.iter().zip(xys).zip(yxs).zip(yys);
+                for (((&xx, &xy), &yx), &yy) in octants {
+                    self.cast_light(x, y, radius, 1, 1.0, 0.0, xx, xy, yx, yy);
+                }
+            }
+            FovAlgorithm::SymmetricShadowcasting => {
+                for &quadrant in &QUADRANTS {
+                    let first_row = Row { depth: 1, start_slope: (-1, 1), end_slope: (1, 1) };
+                    self.scan_symmetric(x, y, radius, quadrant, first_row);
+                }
+            }
+            FovAlgorithm::Permissive => self.compute_permissive(x, y, radius),
+            FovAlgorithm::Raycasting => self.compute_raycasting(x, y, radius),
         }
-        self.map[x as usize][y as usize].1 = true;
+        self.reveal(x, y);
+        self.last_origin = Some((x, y, radius));
+    }
+
+    /// First cell blocking sight on the line from one point to the other,
+    /// the starting cell excluded and the arriving one included.
+    pub fn first_obstacle(&self, from: (i32, i32), to: (i32, i32)) -> Option<(i32, i32)> {
+        bresenham(from, to)
+            .into_iter()
+            .skip(1)
+            .find(|&(x, y)| self.blocks_sight(x, y))
+    }
+
+    /// true if the two cells can see each other, whatever blocks them.
+    /// Lines are traced both ways, so the result is the same from either end.
+    pub fn has_line_of_sight(&self, from: (i32, i32), to: (i32, i32)) -> bool {
+        let is_clear = |line: Vec<(i32, i32)>| {
+            let len = line.len();
+            line.into_iter()
+                .take(len.saturating_sub(1))
+                .skip(1)
+                .all(|(x, y)| !self.blocks_sight(x, y))
+        };
+        is_clear(bresenham(from, to)) || is_clear(bresenham(to, from))
+    }
+
+    fn is_in_bounds(&self, x: i32, y: i32) -> bool {
+        x >= 0 && y >= 0 && x < self.get_width() as i32 && y < self.get_height() as i32
+    }
+
+    /// cells outside of the map block everything
+    fn blocks_sight(&self, x: i32, y: i32) -> bool {
+        !self.is_in_bounds(x, y) || self.is_obstacle(x, y)
     }
 
     fn reset(&mut self) {
-        for i in 0..self.map.len() {
-            for j in 0..self.map[i].len() {
-                self.map[i][j] = (self.map[i][j].0, false);
-            }
+        for (x, y) in self.visible.drain(..) {
+            self.map[x as usize][y as usize].in_sight = false;
         }
     }
 
@@ -52,7 +190,7 @@ impl FovMap {
     }
 
     fn get_height(&self) -> usize {
-        if self.map.len() <= 0 {
+        if self.map.is_empty() {
             0
         } else {
             self.map[0].len()
@@ -60,6 +198,7 @@ impl FovMap {
     }
 
     // Implementation of recursive shadowcasting
+    #[allow(clippy::too_many_arguments)]
     fn cast_light(&mut self, char_x : i32, char_y: i32, radius: i32, row: i32,
                   initial_start_slope: f32, end_slope: f32, // 1.0 / 0.0
                   xx: i32, xy: i32, yx: i32, yy: i32) { // 1 / 0 / 0 / 1
@@ -96,7 +235,7 @@ impl FovMap {
 
                 let radius2 = radius * radius;
                 if (dx * dx + dy * dy) < radius2 {
-                    self.map[map_x as usize][map_y as usize].1 = true;
+                    self.reveal(map_x, map_y);
                 }
 
                 if blocked {
@@ -120,4 +259,372 @@ impl FovMap {
             }
         }
     }
+
+    // Implementation of symmetric shadowcasting, as described in
+    // https://www.albertford.com/shadowcasting/
+    // Slopes are kept as exact fractions so symmetry is not lost to rounding.
+    fn scan_symmetric(&mut self, origin_x: i32, origin_y: i32, radius: i32,
+                      quadrant: Quadrant, mut row: Row) {
+        if row.depth > radius {
+            return;
+        }
+        let mut previous_is_wall: Option<bool> = None;
+        let (min_col, max_col) = row.columns();
+        for col in min_col..(max_col + 1) {
+            let (x, y) = quadrant.transform(origin_x, origin_y, row.depth, col);
+            let is_wall = self.blocks_sight(x, y);
+            let in_radius = row.depth * row.depth + col * col < radius * radius;
+            if self.is_in_bounds(x, y) && in_radius && (is_wall || row.is_symmetric(col)) {
+                self.reveal(x, y);
+            }
+            if previous_is_wall == Some(true) && !is_wall {
+                row.start_slope = slope(row.depth, col);
+            }
+            if previous_is_wall == Some(false) && is_wall {
+                let next_row = Row { end_slope: slope(row.depth, col), ..row.next() };
+                self.scan_symmetric(origin_x, origin_y, radius, quadrant, next_row);
+            }
+            previous_is_wall = Some(is_wall);
+        }
+        if previous_is_wall == Some(false) {
+            self.scan_symmetric(origin_x, origin_y, radius, quadrant, row.next());
+        }
+    }
+
+    fn compute_permissive(&mut self, origin_x: i32, origin_y: i32, radius: i32) {
+        for x in (origin_x - radius)..(origin_x + radius + 1) {
+            for y in (origin_y - radius)..(origin_y + radius + 1) {
+                let (dx, dy) = (x - origin_x, y - origin_y);
+                if !self.is_in_bounds(x, y) || dx * dx + dy * dy >= radius * radius {
+                    continue;
+                }
+                let is_seen = PERMISSIVE_POINTS.iter().any(|&(from_dx, from_dy)| {
+                    PERMISSIVE_POINTS.iter().any(|&(to_dx, to_dy)| {
+                        self.is_line_clear((origin_x as f32 + from_dx, origin_y as f32 + from_dy),
+                                           (x as f32 + to_dx, y as f32 + to_dy),
+                                           (origin_x, origin_y),
+                                           (x, y))
+                    })
+                });
+                if is_seen {
+                    self.reveal(x, y);
+                }
+            }
+        }
+    }
+
+    /// true if no obstacle lies on the line between the two points, without
+    /// considering the cells the line starts and ends in
+    fn is_line_clear(&self, from: (f32, f32), to: (f32, f32),
+                     from_cell: (i32, i32), to_cell: (i32, i32)) -> bool {
+        let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+        let steps = ((dx.abs().max(dy.abs())) * 3.0).ceil() as i32;
+        (1..steps).all(|step| {
+            let progress = step as f32 / steps as f32;
+            let cell = ((from.0 + dx * progress).round() as i32,
+                        (from.1 + dy * progress).round() as i32);
+            cell == from_cell || cell == to_cell || !self.blocks_sight(cell.0, cell.1)
+        })
+    }
+
+    fn compute_raycasting(&mut self, origin_x: i32, origin_y: i32, radius: i32) {
+        let mut targets = vec![];
+        for offset in -radius..(radius + 1) {
+            targets.push((origin_x + offset, origin_y - radius));
+            targets.push((origin_x + offset, origin_y + radius));
+            targets.push((origin_x - radius, origin_y + offset));
+            targets.push((origin_x + radius, origin_y + offset));
+        }
+        for (target_x, target_y) in targets {
+            for (x, y) in bresenham((origin_x, origin_y), (target_x, target_y)) {
+                let (dx, dy) = (x - origin_x, y - origin_y);
+                if !self.is_in_bounds(x, y) || dx * dx + dy * dy >= radius * radius {
+                    break;
+                }
+                self.reveal(x, y);
+                if self.is_obstacle(x, y) {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// One of the four directions symmetric shadowcasting scans in
+#[derive(Clone, Copy, Debug)]
+enum Quadrant {
+    North,
+    East,
+    South,
+    West,
+}
+
+const QUADRANTS : [Quadrant; 4] = [Quadrant::North, Quadrant::East, Quadrant::South, Quadrant::West];
+
+impl Quadrant {
+    /// convert a (row, column) position in the quadrant to map coordinates
+    fn transform(self, origin_x: i32, origin_y: i32, depth: i32, col: i32) -> (i32, i32) {
+        match self {
+            Quadrant::North => (origin_x + col, origin_y - depth),
+            Quadrant::South => (origin_x + col, origin_y + depth),
+            Quadrant::East => (origin_x + depth, origin_y + col),
+            Quadrant::West => (origin_x - depth, origin_y + col),
+        }
+    }
+}
+
+/// a fraction, as (numerator, denominator) with a positive denominator
+type Slope = (i32, i32);
+
+fn slope(depth: i32, col: i32) -> Slope {
+    (2 * col - 1, 2 * depth)
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Row {
+    depth: i32,
+    start_slope: Slope,
+    end_slope: Slope,
+}
+
+impl Row {
+    /// first and last columns of the row between the two slopes
+    fn columns(&self) -> (i32, i32) {
+        // round_ties_up(depth * start) and round_ties_down(depth * end)
+        let (start_num, start_den) = self.start_slope;
+        let (end_num, end_den) = self.end_slope;
+        let min_col = (2 * self.depth * start_num + start_den).div_euclid(2 * start_den);
+        let max_col = -(end_den - 2 * self.depth * end_num).div_euclid(2 * end_den);
+        (min_col, max_col)
+    }
+
+    fn is_symmetric(&self, col: i32) -> bool {
+        let (start_num, start_den) = self.start_slope;
+        let (end_num, end_den) = self.end_slope;
+        col * start_den >= self.depth * start_num && col * end_den <= self.depth * end_num
+    }
+
+    fn next(&self) -> Row {
+        Row { depth: self.depth + 1, ..*self }
+    }
+}
+
+/// cells on the line between two points, both included
+pub fn bresenham(from: (i32, i32), to: (i32, i32)) -> Vec<(i32, i32)> {
+    let (mut x, mut y) = from;
+    let dx = (to.0 - x).abs();
+    let dy = -(to.1 - y).abs();
+    let step_x = if x < to.0 { 1 } else { -1 };
+    let step_y = if y < to.1 { 1 } else { -1 };
+    let mut error = dx + dy;
+    let mut cells = vec![(x, y)];
+    while (x, y) != to {
+        let double_error = 2 * error;
+        if double_error >= dy {
+            error += dy;
+            x += step_x;
+        }
+        if double_error <= dx {
+            error += dx;
+            y += step_y;
+        }
+        cells.push((x, y));
+    }
+    cells
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALGORITHMS : [FovAlgorithm; 4] = [
+        FovAlgorithm::Shadowcasting,
+        FovAlgorithm::SymmetricShadowcasting,
+        FovAlgorithm::Permissive,
+        FovAlgorithm::Raycasting,
+    ];
+
+    /// `#` are obstacles and `@` is the viewer, returned alongside the map
+    fn fixture(rows: &[&str]) -> (FovMap, (i32, i32)) {
+        let mut fov_map = FovMap::new(rows[0].len() as i32, rows.len() as i32);
+        let mut viewer = (0, 0);
+        for (y, row) in rows.iter().enumerate() {
+            for (x, c) in row.chars().enumerate() {
+                fov_map.set(x as i32, y as i32, c == '#');
+                if c == '@' {
+                    viewer = (x as i32, y as i32);
+                }
+            }
+        }
+        (fov_map, viewer)
+    }
+
+    fn compute(rows: &[&str], algorithm: FovAlgorithm, radius: i32) -> FovMap {
+        let (mut fov_map, (x, y)) = fixture(rows);
+        fov_map.set_algorithm(algorithm);
+        fov_map.compute_fov(x, y, radius);
+        fov_map
+    }
+
+    const ROOM : [&str; 9] = [
+        "#########",
+        "#.......#",
+        "#.......#",
+        "#.......#",
+        "#...@...#",
+        "#.......#",
+        "#.......#",
+        "#.......#",
+        "#########",
+    ];
+
+    const TWO_ROOMS : [&str; 7] = [
+        "###########",
+        "#...#.....#",
+        "#...#.....#",
+        "#.@.#.....#",
+        "#...#.....#",
+        "#...#.....#",
+        "###########",
+    ];
+
+    const PILLARS : [&str; 11] = [
+        "###############",
+        "#.............#",
+        "#..#.....#....#",
+        "#.......#.....#",
+        "#....#........#",
+        "#......@..#...#",
+        "#.#...........#",
+        "#.....#....#..#",
+        "#..#......#...#",
+        "#.............#",
+        "###############",
+    ];
+
+    #[test]
+    fn every_algorithm_sees_a_whole_open_room() {
+        for &algorithm in &ALGORITHMS {
+            let fov_map = compute(&ROOM, algorithm, 10);
+            for x in 0..9 {
+                for y in 0..9 {
+                    assert!(fov_map.is_in_sight(x, y), "{:?} does not see ({}, {})", algorithm, x, y);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn no_algorithm_sees_through_walls() {
+        for &algorithm in &ALGORITHMS {
+            let fov_map = compute(&TWO_ROOMS, algorithm, 20);
+            assert!(fov_map.is_in_sight(4, 3), "{:?} does not see the separating wall", algorithm);
+            for x in 5..10 {
+                for y in 1..6 {
+                    assert!(!fov_map.is_in_sight(x, y), "{:?} sees ({}, {})", algorithm, x, y);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn every_algorithm_respects_the_radius() {
+        for &algorithm in &ALGORITHMS {
+            let fov_map = compute(&ROOM, algorithm, 3);
+            assert!(fov_map.is_in_sight(4, 4));
+            assert!(fov_map.is_in_sight(6, 4), "{:?}", algorithm);
+            assert!(!fov_map.is_in_sight(7, 7), "{:?}", algorithm);
+            assert!(!fov_map.is_in_sight(8, 4), "{:?}", algorithm);
+        }
+    }
+
+    #[test]
+    fn cell_right_behind_a_pillar_is_hidden() {
+        let rows = [
+            "###########",
+            "#.........#",
+            "#.@.#.....#",
+            "#.........#",
+            "###########",
+        ];
+        for &algorithm in &ALGORITHMS {
+            let fov_map = compute(&rows, algorithm, 10);
+            assert!(fov_map.is_in_sight(4, 2), "{:?} does not see the pillar", algorithm);
+            assert!(!fov_map.is_in_sight(6, 2), "{:?} sees behind the pillar", algorithm);
+        }
+    }
+
+    #[test]
+    fn symmetric_shadowcasting_sees_the_least_floor() {
+        let (obstacles, _) = fixture(&PILLARS);
+        let symmetric = compute(&PILLARS, FovAlgorithm::SymmetricShadowcasting, 20);
+        for &algorithm in &ALGORITHMS {
+            let fov_map = compute(&PILLARS, algorithm, 20);
+            for &(x, y) in symmetric.visible_cells() {
+                if !obstacles.is_obstacle(x, y) {
+                    assert!(fov_map.is_in_sight(x, y), "{:?} does not see ({}, {})", algorithm, x, y);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn bresenham_goes_from_one_end_to_the_other() {
+        assert_eq!(bresenham((1, 1), (5, 3)), vec![(1, 1), (2, 2), (3, 2), (4, 3), (5, 3)]);
+        assert_eq!(bresenham((2, 2), (2, 2)), vec![(2, 2)]);
+    }
+
+    #[test]
+    fn first_obstacle_is_found_along_the_line() {
+        let (fov_map, _) = fixture(&TWO_ROOMS);
+        assert_eq!(fov_map.first_obstacle((2, 3), (8, 3)), Some((4, 3)));
+        assert_eq!(fov_map.first_obstacle((2, 3), (2, 1)), None);
+        assert_eq!(fov_map.first_obstacle((2, 3), (2, 0)), Some((2, 0)));
+    }
+
+    #[test]
+    fn line_of_sight_is_symmetric() {
+        let (fov_map, _) = fixture(&PILLARS);
+        for x in 1..14 {
+            for y in 1..10 {
+                assert_eq!(fov_map.has_line_of_sight((7, 5), (x, y)),
+                           fov_map.has_line_of_sight((x, y), (7, 5)),
+                           "({}, {})", x, y);
+            }
+        }
+        assert!(fov_map.has_line_of_sight((7, 5), (1, 5)));
+        assert!(!fov_map.has_line_of_sight((7, 5), (12, 5)));
+        // a wall can be seen, even though it blocks
+        assert!(fov_map.has_line_of_sight((7, 5), (10, 5)));
+    }
+
+    #[test]
+    fn shadowcasting_is_not_symmetric() {
+        let (mut fov_map, _) = fixture(&PILLARS);
+        fov_map.compute_fov(1, 1, 20);
+        assert!(fov_map.is_in_sight(2, 7));
+        fov_map.compute_fov(2, 7, 20);
+        assert!(!fov_map.is_in_sight(1, 1));
+    }
+
+    #[test]
+    fn symmetric_shadowcasting_is_symmetric() {
+        let (obstacles, _) = fixture(&PILLARS);
+        let floor: Vec<(i32, i32)> = (0..15)
+            .flat_map(|x| (0..11).map(move |y| (x, y)))
+            .filter(|&(x, y)| !obstacles.is_obstacle(x, y))
+            .collect();
+
+        let mut from = obstacles.clone();
+        from.set_algorithm(FovAlgorithm::SymmetricShadowcasting);
+        let mut back = from.clone();
+        for &(x, y) in &floor {
+            from.compute_fov(x, y, 20);
+            for &(other_x, other_y) in &floor {
+                back.compute_fov(other_x, other_y, 20);
+                assert_eq!(from.is_in_sight(other_x, other_y), back.is_in_sight(x, y),
+                           "({}, {}) and ({}, {})", x, y, other_x, other_y);
+            }
+        }
+    }
 }
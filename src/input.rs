@@ -1,18 +1,35 @@
-use crate::constants::PLAYER;
+use crate::constants::{
+    ATTACK_COST,
+    LEVEL_SCREEN_WIDTH,
+    LEVEL_UP_HP,
+    MAP_HEIGHT,
+    MAP_WIDTH,
+    MOVE_COST,
+    PLAYER,
+    TURN_ENERGY,
+};
 use tcod::input::{
     Key,
     KeyCode,
     Mouse,
 };
 use crate::element::*;
-use crate::fov::FovMap;
+use crate::faction::Faction;
 use crate::map::{
     Map,
-    move_by,
+    close_door,
+    walk_by,
 };
+use crate::pathfinding::{DIAGONAL_MOVES, ORTHOGONAL_MOVES};
+use crate::status::{self, StatusKind};
+use crate::terrain::Terrain;
+use crate::travel::{AutoMove, auto_move_step};
 use crate::utils::mut_two;
 use crate::render::*;
 use crate::state::*;
+use crate::messages::MessageLog;
+use rand::Rng;
+use tcod::colors;
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum PlayerAction {
@@ -21,22 +38,42 @@ pub enum PlayerAction {
     Exit,
 }
 
-/// return a string with the names of all elements under the mouse
-pub fn get_names_under_mouse(mouse: Mouse, elements: &[Element], fov_map: &FovMap) -> Vec<String> {
+/// return a string with the names of all elements under the mouse, followed
+/// by the name of the terrain there
+pub fn get_names_under_mouse(mouse: Mouse, elements: &[Element], map: &Map) -> Vec<String> {
     let (x, y) = (mouse.cx as i32, mouse.cy as i32);
 
     // create a list with the names of all elements at the mouse's coordinates and in FOV
-    elements
+    let mut names = elements
         .iter()
         .filter(|elt| {
             elt.pos() == (x, y) &&
-            fov_map.is_in_sight(elt.position.x, elt.position.y)
+            map.is_in_sight(elt.position.x, elt.position.y) &&
+            status::is_seen_by(elt, &elements[PLAYER])
         })
         .map(|elt| elt.display_name.clone())
-        .collect::<Vec<_>>()
+        .collect::<Vec<_>>();
+
+    if x >= 0 && y >= 0 && x < MAP_WIDTH && y < MAP_HEIGHT {
+        if let Some(memory) = map.memory(x, y).filter(|_| !map.is_in_sight(x, y)) {
+            names.push(format!("{} (remembered)", memory.display_name));
+        }
+        if map.is_explored(x, y) {
+            names.push(map[x as usize][y as usize].terrain.info().name.to_owned());
+        }
+    }
+    names
 }
 
-fn player_move_or_attack(map: &Map, elements: &mut [Element], dx: i32, dy: i32) {
+pub(crate) fn player_move_or_attack(game_state: &mut GameState, dx: i32, dy: i32) {
+    // confused players stumble in any direction
+    let (dx, dy) = if status::has(&game_state.elements[PLAYER], StatusKind::Confused) {
+        let directions: Vec<_> = ORTHOGONAL_MOVES.iter().chain(DIAGONAL_MOVES.iter()).collect();
+        *directions[game_state.rng.gen_range(0, directions.len())]
+    } else {
+        (dx, dy)
+    };
+    let elements = &mut game_state.elements;
     let (x, y) = elements[PLAYER].pos();
     let new_x = x + dx;
     let new_y = y + dy;
@@ -46,12 +83,20 @@ fn player_move_or_attack(map: &Map, elements: &mut [Element], dx: i32, dy: i32)
     });
 
     match target_id {
+        // allies step aside
+        Some(ally_id) if elements[ally_id].faction == Faction::Player => {
+            elements[ally_id].set_pos(x, y);
+            elements[PLAYER].set_pos(new_x, new_y);
+            let terrain = game_state.map[new_x as usize][new_y as usize].terrain;
+            elements[PLAYER].energy -= terrain.info().movement_cost * MOVE_COST;
+        }
         Some(target_id) => {
             let (player, target) = mut_two(PLAYER, target_id, elements);
-            player.attack(target);
+            player.attack(target, &mut game_state.rng, &mut game_state.log);
+            player.energy -= ATTACK_COST;
         }
         None => {
-            move_by(PLAYER, map, elements, dx, dy);
+            walk_by(PLAYER, &mut game_state.map, elements, dx, dy);
         }
     }
 }
@@ -63,40 +108,72 @@ pub fn handle_keys_player_mode(
 ) -> PlayerAction {
     use PlayerAction::*;
 
+    // stunned players lose a turn for each key they press, but can still
+    // quit, and confused ones cannot travel
+    let player = &game_state.elements[PLAYER];
+    if status::has(player, StatusKind::Stunned) {
+        game_state.auto_move = None;
+        if key.code == KeyCode::Escape {
+            return Exit;
+        }
+        if !key.pressed {
+            return DidntTakeTurn;
+        }
+        game_state.log.add("You are stunned and lose your turn.", colors::YELLOW);
+        game_state.elements[PLAYER].energy -= TURN_ENERGY;
+        return TookTurn;
+    }
+    if status::has(player, StatusKind::Confused) {
+        game_state.auto_move = None;
+    }
+
+    // any key interrupts an automatic move, otherwise it goes on
+    if let Some(auto_move) = game_state.auto_move {
+        if key.pressed {
+            game_state.auto_move = None;
+            return DidntTakeTurn;
+        }
+        if auto_move_step(game_state, auto_move) {
+            return TookTurn;
+        }
+        game_state.auto_move = None;
+        return DidntTakeTurn;
+    }
+
     match key {
         // NSWE
         Key { code: KeyCode::Up, .. } | Key { code: KeyCode::NumPad8, .. } => {
-            player_move_or_attack(&game_state.map, &mut game_state.elements, 0, -1);
+            player_move_or_attack(game_state, 0, -1);
             TookTurn
         }
         Key { code: KeyCode::Down, .. } | Key { code: KeyCode::NumPad2, .. } => {
-            player_move_or_attack(&game_state.map, &mut game_state.elements, 0, 1);
+            player_move_or_attack(game_state, 0, 1);
             TookTurn
         }
         Key { code: KeyCode::Left, .. } | Key { code: KeyCode::NumPad4, .. } => {
-            player_move_or_attack(&game_state.map, &mut game_state.elements, -1, 0);
+            player_move_or_attack(game_state, -1, 0);
             TookTurn
         }
         Key { code: KeyCode::Right, .. } | Key { code: KeyCode::NumPad6, .. } => {
-            player_move_or_attack(&game_state.map, &mut game_state.elements, 1, 0);
+            player_move_or_attack(game_state, 1, 0);
             TookTurn
         }
 
         // Diagonals
         Key { code: KeyCode::NumPad7, .. }=> {
-            player_move_or_attack(&game_state.map, &mut game_state.elements, -1, -1);
+            player_move_or_attack(game_state, -1, -1);
             TookTurn
         }
         Key { code: KeyCode::NumPad9, .. } => {
-            player_move_or_attack(&game_state.map, &mut game_state.elements, 1, -1);
+            player_move_or_attack(game_state, 1, -1);
             TookTurn
         }
         Key { code: KeyCode::NumPad1, .. } => {
-            player_move_or_attack(&game_state.map, &mut game_state.elements, -1, 1);
+            player_move_or_attack(game_state, -1, 1);
             TookTurn
         }
         Key { code: KeyCode::NumPad3, .. } => {
-            player_move_or_attack(&game_state.map, &mut game_state.elements, 1, 1);
+            player_move_or_attack(game_state, 1, 1);
             TookTurn
         }
 
@@ -116,10 +193,14 @@ pub fn handle_keys_player_mode(
                 &game_state.inventory,
                 "Press the key next to an item to use it, or any other to cancel.\n",
                 root);
-            if let Some(inventory_index) = inventory_index {
-                use_item(inventory_index, &mut game_state.inventory, &mut game_state.elements);
+            match inventory_index {
+                Some(inventory_index) if use_item(inventory_index,
+                                                  &mut game_state.inventory,
+                                                  &mut game_state.elements,
+                                                  &game_state.map,
+                                                  &mut game_state.log) => TookTurn,
+                _ => DidntTakeTurn,
             }
-            DidntTakeTurn
         }
 
         Key { printable: 'd', .. } => {
@@ -134,11 +215,103 @@ pub fn handle_keys_player_mode(
             DidntTakeTurn
         }
 
+        Key { printable: 't', .. } => {
+            toggle_torch(game_state);
+            TookTurn
+        }
+
+        Key { printable: 'c', .. } => {
+            if player_close_door(game_state) {
+                game_state.elements[PLAYER].energy -= TURN_ENERGY;
+                TookTurn
+            } else {
+                game_state.log.add("There is no open door next to you.", colors::WHITE);
+                DidntTakeTurn
+            }
+        }
+
+        Key { printable: 'x', .. } => {
+            game_state.auto_move = Some(AutoMove::Explore);
+            DidntTakeTurn
+        }
+
+        Key { printable: 'r', .. } => {
+            game_state.auto_move = Some(AutoMove::Rest);
+            DidntTakeTurn
+        }
+
+        Key { printable: '>', .. } => {
+            // go down the stairs, or walk to them
+            let (x, y) = game_state.elements[PLAYER].pos();
+            if game_state.map[x as usize][y as usize].terrain == Terrain::Stairs {
+                game_state.descend();
+            } else {
+                game_state.auto_move = Some(AutoMove::Stairs);
+            }
+            DidntTakeTurn
+        }
+
         Key { code: KeyCode::Escape, .. } => Exit,
         _ => DidntTakeTurn,
     }
 }
 
+/// Douse the player's torch, so they are harder to notice in the dark, or
+/// light it again.
+pub(crate) fn toggle_torch(game_state: &mut GameState) {
+    let player = &mut game_state.elements[PLAYER];
+    if player.light.take().is_some() {
+        game_state.log.add("You douse your torch and sink into the shadows.", colors::WHITE);
+    } else {
+        player.light = Some(make_torch());
+        game_state.log.add("You light your torch.", colors::WHITE);
+    }
+    player.energy -= TURN_ENERGY;
+}
+
+/// close the first open door found around the player
+fn player_close_door(game_state: &mut GameState) -> bool {
+    let (x, y) = game_state.elements[PLAYER].pos();
+    for dx in -1..2 {
+        for dy in -1..2 {
+            let (door_x, door_y) = (x + dx, y + dy);
+            if game_state.map[door_x as usize][door_y as usize].terrain == Terrain::OpenDoor &&
+                close_door(door_x, door_y, &mut game_state.map, &game_state.elements) {
+                return true;
+            }
+        }
+    }
+    false
+}
+
+/// Let the player choose a stat to raise for every level they reached.
+pub fn level_up(root: &mut tcod::console::Root, game_state: &mut GameState) {
+    while game_state.can_level_up() {
+        let player = &game_state.elements[PLAYER];
+        let fighter = match player.fighter {
+            Some(fighter) => fighter,
+            None => return,
+        };
+        let header = format!("Level up! You reach level {}. Choose a stat to raise:\n", player.level + 1);
+        let options = [
+            format!("Max HP (+{}, from {})", LEVEL_UP_HP, fighter.max_hp),
+            format!("Strength (+1 damage, from {})", fighter.damage),
+            format!("Defense (+1, from {})", fighter.defense),
+        ];
+        let boosts = [StatBoost::MaxHp, StatBoost::Strength, StatBoost::Defense];
+        // no way out without choosing, but closing the window
+        let choice = loop {
+            if root.window_closed() {
+                return;
+            }
+            if let Some(choice) = menu(&header, &options, LEVEL_SCREEN_WIDTH, root) {
+                break choice;
+            }
+        };
+        game_state.level_up(boosts[choice]);
+    }
+}
+
 pub fn handle_keys_dead_mode(key: Key) -> PlayerAction {
     use PlayerAction::*;
 
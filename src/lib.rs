@@ -0,0 +1,28 @@
+extern crate tcod;
+extern crate rand;
+
+pub mod ability;
+pub mod ai;
+pub mod colors;
+pub mod constants;
+pub mod damage;
+pub mod dice;
+pub mod dijkstra;
+pub mod effects;
+pub mod element;
+pub mod faction;
+pub mod fov;
+pub mod input;
+pub mod light;
+pub mod map;
+pub mod messages;
+pub mod pathfinding;
+pub mod position;
+pub mod render;
+pub mod scheduler;
+pub mod state;
+pub mod status;
+pub mod terrain;
+pub mod travel;
+pub mod utils;
+pub mod vault;
@@ -0,0 +1,103 @@
+use crate::fov::FovMap;
+use tcod::colors::{self, Color};
+
+/// below this level on every channel, a tile is too dark to be seen
+const MIN_VISIBLE_LIGHT: u8 = 16;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Light {
+    pub radius: i32,
+    pub color: Color,
+}
+
+/// a light and the position it shines from
+pub type LightSource = (i32, i32, Light);
+
+/// Light reaching each tile of the map, from every source at once
+#[derive(Clone, Debug)]
+pub struct LightMap {
+    levels: Vec<Vec<Color>>,
+    /// sources used for the last computation, if still valid
+    last_sources: Option<Vec<LightSource>>,
+    /// casts each light, kept between updates to spare an allocation
+    light_fov: FovMap,
+}
+
+impl LightMap {
+    pub fn new(width: i32, height: i32) -> Self {
+        LightMap {
+            levels: vec![vec![colors::BLACK; height as usize]; width as usize],
+            last_sources: None,
+            light_fov: FovMap::new(width, height),
+        }
+    }
+
+    /// force the next update to recompute everything, e.g. after the
+    /// obstacles changed
+    pub fn invalidate(&mut self) {
+        self.last_sources = None;
+    }
+
+    /// Recompute the light levels if the sources changed since last time.
+    /// Returns true if they were recomputed.
+    pub fn update(&mut self, obstacles: &FovMap, sources: Vec<LightSource>) -> bool {
+        if self.last_sources.as_ref() == Some(&sources) {
+            return false;
+        }
+        for column in self.levels.iter_mut() {
+            for level in column.iter_mut() {
+                *level = colors::BLACK;
+            }
+        }
+
+        // each light is cast with the same obstacles as the player's FOV
+        let light_fov = &mut self.light_fov;
+        light_fov.copy_obstacles(obstacles);
+        for &(source_x, source_y, light) in &sources {
+            light_fov.compute_fov(source_x, source_y, light.radius);
+            for &(x, y) in light_fov.visible_cells() {
+                let (dx, dy) = ((x - source_x) as f32, (y - source_y) as f32);
+                let intensity = 1.0 - (dx * dx + dy * dy).sqrt() / (light.radius + 1) as f32;
+                let level = &mut self.levels[x as usize][y as usize];
+                *level = add_light(*level, light.color, intensity);
+            }
+        }
+        self.last_sources = Some(sources);
+        true
+    }
+
+    pub fn level(&self, x: i32, y: i32) -> Color {
+        self.levels[x as usize][y as usize]
+    }
+
+    pub fn is_lit(&self, x: i32, y: i32) -> bool {
+        let level = self.level(x, y);
+        level.r >= MIN_VISIBLE_LIGHT || level.g >= MIN_VISIBLE_LIGHT || level.b >= MIN_VISIBLE_LIGHT
+    }
+}
+
+fn add_light(level: Color, light: Color, intensity: f32) -> Color {
+    let add = |channel: u8, light_channel: u8| {
+        (channel as f32 + light_channel as f32 * intensity.max(0.0)).min(255.0) as u8
+    };
+    Color {
+        r: add(level.r, light.r),
+        g: add(level.g, light.g),
+        b: add(level.b, light.b),
+    }
+}
+
+/// The color of a lit tile: its normal color filtered by the light reaching
+/// it, but never darker than how it is remembered.
+/// Strong lights can make it up to twice as bright as its normal color.
+pub fn shade(lit_color: Color, dark_color: Color, light: Color) -> Color {
+    let filter = |channel: u8, light_channel: u8, dark_channel: u8| {
+        let lit = (channel as u32 * light_channel as u32 / 128).min(255) as u8;
+        lit.max(dark_channel)
+    };
+    Color {
+        r: filter(lit_color.r, light.r, dark_color.r),
+        g: filter(lit_color.g, light.g, dark_color.g),
+        b: filter(lit_color.b, light.b, dark_color.b),
+    }
+}
@@ -1,20 +1,9 @@
+extern crate roguelike_test;
 extern crate tcod;
-extern crate rand;
 
-mod ai;
-mod colors;
-mod constants;
-mod element;
-mod fov;
-mod input;
-mod map;
-mod messages;
-mod position;
-mod render;
-mod state;
-mod utils;
-
-use crate::{
+use roguelike_test::{
+    render,
+    scheduler,
     // state::{GameState, new_game},
     state::new_game,
     constants::{
@@ -25,18 +14,18 @@ use crate::{
         PLAYER,
         SCREEN_HEIGHT,
         SCREEN_WIDTH,
-        TORCH_RADIUS,
     },
     input::{
         get_names_under_mouse,
         handle_keys_dead_mode,
         handle_keys_player_mode,
+        level_up,
         PlayerAction,
     },
-    map::explore,
 };
 use tcod::console::*;
 use tcod::input::{self as tcodInput, Event};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 fn main() {
     let mut root = Root::initializer()
@@ -53,14 +42,12 @@ fn main() {
 
     tcod::system::set_fps(LIMIT_FPS);
 
-    let mut game_state = new_game();
+    let seed = SystemTime::now().duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as usize)
+        .unwrap_or(0);
+    let mut game_state = new_game(seed);
+    game_state.update_fov();
     while !root.window_closed() {
-        let player = &game_state.elements[PLAYER];
-        game_state.fov_map.compute_fov(player.position.x,
-                                       player.position.y,
-                                       TORCH_RADIUS);
-        explore(&mut game_state.map, &game_state.fov_map);
-
         match tcodInput::check_for_event(tcodInput::MOUSE | tcodInput::KEY_PRESS) {
             Some((_, Event::Mouse(m))) => mouse = m,
             Some((_, Event::Key(k))) => key = k,
@@ -69,7 +56,7 @@ fn main() {
 
         let hovered = get_names_under_mouse(mouse,
                                             &game_state.elements,
-                                            &game_state.fov_map);
+                                            &game_state.map);
         render::render_all(&mut root,
                            &mut con_map,
                            &mut panel,
@@ -86,13 +73,12 @@ fn main() {
             break;
         }
 
-        // let monsters take their turn
+        // let monsters act until the player's next turn
         if game_state.elements[PLAYER].alive && player_action != PlayerAction::DidntTakeTurn {
-            for id in 0..game_state.elements.len() {
-                if game_state.elements[id].ai.is_some() {
-                    ai::ai_take_turn(id, &game_state.map, & mut game_state.elements, PLAYER, &game_state.fov_map);
-                }
-            }
+            scheduler::pass_time(&mut game_state);
+        }
+        if game_state.elements[PLAYER].alive {
+            level_up(&mut root, &mut game_state);
         }
     }
 }
@@ -1,7 +1,10 @@
-extern crate rand;
-
 use crate::{
     constants::{
+        CAVE_FUNGUS_CHANCE,
+        CAVE_INITIAL_WALL_CHANCE,
+        CAVE_SMOOTHING_STEPS,
+        FOV_ALGORITHM,
+        HARMFUL_TERRAIN_COST,
         MAP_WIDTH,
         MAP_HEIGHT,
         ROOM_MAX_SIZE,
@@ -9,38 +12,257 @@ use crate::{
         MAX_ROOMS,
         MAX_ROOM_MONSTERS,
         MAX_ROOM_ITEMS,
+        MONSTER_TORCH_RADIUS,
+        MOVE_COST,
+        PLAYER,
+        TORCH_BEARER_CHANCE,
+        TURN_ENERGY,
+        VAULT_CHANCE,
     },
     element::*,
-    fov::FovMap,
+    faction::Faction,
+    fov::{FovMap, bresenham as line},
+    colors::COLOR_TORCH,
+    light::{Light, LightMap, LightSource},
+    status,
+    terrain::Terrain,
+    utils::GameRng,
+    vault::{Vault, VaultCell, load_vaults},
 };
 use rand::Rng;
 use std::cmp;
-
-pub type Map = Vec<Vec<Tile>>;
+use std::ops::Index;
+use tcod::colors::Color;
 
 #[derive(Clone, Copy, Debug)]
 pub struct Tile {
-    pub block_movement: bool,
-    pub block_sight: bool,
-    pub explored: bool,
+    pub terrain: Terrain,
 }
 
 impl Tile {
-    pub fn empty() -> Self {
-        Tile { block_movement: false, block_sight: false, explored: false }
+    pub fn new(terrain: Terrain) -> Self {
+        Tile { terrain }
+    }
+
+    pub fn block_movement(&self) -> bool {
+        !self.terrain.info().walkable
     }
-    pub fn wall() -> Self {
-        Tile { block_movement: true, block_sight: true, explored: false }
+
+    pub fn block_sight(&self) -> bool {
+        !self.terrain.info().transparent
+    }
+
+    /// true when the tile can be walked on, opening a door if needed
+    pub fn is_passable(&self) -> bool {
+        !self.block_movement() || self.terrain == Terrain::Door
     }
+
+    /// Cost of a path through the tile: the turns needed to walk on it,
+    /// including opening it if it is a closed door, and much more if it is
+    /// harmful. None if it cannot be walked on.
+    pub fn movement_cost(&self) -> Option<i32> {
+        match self.terrain {
+            Terrain::Door => Some(1 + Terrain::OpenDoor.info().movement_cost),
+            _ if self.block_movement() => None,
+            terrain if terrain.info().damage.is_some() => {
+                Some(terrain.info().movement_cost + HARMFUL_TERRAIN_COST)
+            }
+            terrain => Some(terrain.info().movement_cost),
+        }
+    }
+}
+
+/// The level's tiles, along with the FOV and the lights they are seen through.
+/// Tiles can be read with `map[x][y]` but can only be changed through
+/// `set_terrain`, so the FOV obstacles and lights always match the terrain.
+#[derive(Clone, Debug)]
+pub struct Map {
+    tiles: Vec<Vec<Tile>>,
+    fov_map: FovMap,
+    light_map: LightMap,
+    /// lights emitted by the terrain itself
+    terrain_lights: Vec<LightSource>,
+    /// what the player last saw standing on each tile, see `remember`
+    memories: Vec<Vec<Option<Memory>>>,
 }
 
+/// An element as the player last saw it, drawn on explored tiles out of sight
+#[derive(Clone, Debug)]
+pub struct Memory {
+    pub char: char,
+    pub color: Color,
+    pub display_name: String,
+}
+
+impl Map {
+    /// a map made only of walls
+    pub fn new(width: i32, height: i32) -> Self {
+        let mut fov_map = FovMap::new(width, height);
+        fov_map.set_algorithm(FOV_ALGORITHM);
+        for x in 0..width {
+            for y in 0..height {
+                fov_map.set(x, y, true);
+            }
+        }
+        Map {
+            tiles: vec![vec![Tile::new(Terrain::Wall); height as usize]; width as usize],
+            fov_map,
+            light_map: LightMap::new(width, height),
+            terrain_lights: vec![],
+            memories: vec![vec![None; height as usize]; width as usize],
+        }
+    }
+
+    pub fn width(&self) -> i32 {
+        self.tiles.len() as i32
+    }
+
+    pub fn height(&self) -> i32 {
+        self.tiles.first().map_or(0, |column| column.len() as i32)
+    }
+
+    pub fn is_in_bounds(&self, x: i32, y: i32) -> bool {
+        x >= 0 && y >= 0 && x < self.width() && y < self.height()
+    }
+
+    /// true if a diagonal step from the given tile would cut the corner of a
+    /// tile blocking movement
+    pub fn cuts_corner(&self, x: i32, y: i32, dx: i32, dy: i32) -> bool {
+        dx != 0 && dy != 0 &&
+            (self.tiles[x as usize][(y + dy) as usize].block_movement() ||
+             self.tiles[(x + dx) as usize][y as usize].block_movement())
+    }
+
+    pub fn set_terrain(&mut self, x: i32, y: i32, terrain: Terrain) {
+        let tile = Tile::new(terrain);
+        self.tiles[x as usize][y as usize] = tile;
+        self.fov_map.set(x, y, tile.block_sight());
+
+        self.terrain_lights.retain(|&(light_x, light_y, _)| (light_x, light_y) != (x, y));
+        if let Some(light) = terrain.info().light {
+            self.terrain_lights.push((x, y, light));
+        }
+        self.light_map.invalidate();
+    }
+
+    /// Update what is in sight from the given position, lit by the terrain
+    /// and by the given lights.
+    /// Cheap when neither the viewer, the lights nor the terrain changed.
+    pub fn update_fov(&mut self, x: i32, y: i32, radius: i32, lights: &[LightSource]) {
+        let sources = self.terrain_lights.iter().chain(lights.iter()).cloned().collect();
+        let fov_changed = self.fov_map.update_fov(x, y, radius);
+        let lights_changed = self.light_map.update(&self.fov_map, sources);
+        if fov_changed || lights_changed {
+            for (visible_x, visible_y) in self.fov_map.visible_cells().to_vec() {
+                if self.light_map.is_lit(visible_x, visible_y) {
+                    self.fov_map.explore(visible_x, visible_y);
+                }
+            }
+        }
+    }
+
+    /// tiles both in line of sight and lit
+    pub fn visible_cells(&self) -> impl Iterator<Item = (i32, i32)> + '_ {
+        self.fov_map.visible_cells()
+            .iter()
+            .cloned()
+            .filter(move |&(x, y)| self.light_map.is_lit(x, y))
+    }
+
+    pub fn is_in_sight(&self, x: i32, y: i32) -> bool {
+        self.fov_map.is_in_sight(x, y) && self.light_map.is_lit(x, y)
+    }
+
+    pub fn is_explored(&self, x: i32, y: i32) -> bool {
+        self.fov_map.is_explored(x, y)
+    }
+
+    pub fn is_lit(&self, x: i32, y: i32) -> bool {
+        self.light_map.is_lit(x, y)
+    }
+
+    /// true if nothing blocks sight between the two tiles, lit or not
+    pub fn has_line_of_sight(&self, from: (i32, i32), to: (i32, i32)) -> bool {
+        self.fov_map.has_line_of_sight(from, to)
+    }
+
+    /// First tile blocking sight on the line between the two tiles, where
+    /// a projectile would stop.
+    pub fn first_blocking_cell(&self, from: (i32, i32), to: (i32, i32)) -> Option<(i32, i32)> {
+        self.fov_map.first_obstacle(from, to)
+    }
+
+    /// First element blocking the way on the line between the two tiles,
+    /// the one a projectile would hit.
+    /// None if the line reaches a blocking tile before any element.
+    pub fn first_blocking_element(&self,
+                                  from: (i32, i32),
+                                  to: (i32, i32),
+                                  elements: &[Element]) -> Option<usize> {
+        for (x, y) in line(from, to).into_iter().skip(1) {
+            if self.fov_map.is_obstacle(x, y) {
+                return None;
+            }
+            let hit = elements.iter().position(|element| {
+                element.block_movement && element.pos() == (x, y)
+            });
+            if hit.is_some() {
+                return hit;
+            }
+        }
+        None
+    }
+
+    /// Remember what stands on each tile in sight, blocking elements over the
+    /// items under them, and forget what is not there anymore.
+    /// The player is never remembered.
+    pub fn remember(&mut self, elements: &[Element]) {
+        for (x, y) in self.visible_cells().collect::<Vec<_>>() {
+            self.memories[x as usize][y as usize] = None;
+        }
+        for element in elements.iter().skip(PLAYER + 1) {
+            let (x, y) = element.pos();
+            if !self.is_in_sight(x, y) || !status::is_seen_by(element, &elements[PLAYER]) {
+                continue;
+            }
+            let memory = &mut self.memories[x as usize][y as usize];
+            if memory.is_none() || element.block_movement {
+                *memory = Some(Memory {
+                    char: element.char,
+                    color: element.color,
+                    display_name: element.display_name.clone(),
+                });
+            }
+        }
+    }
+
+    pub fn memory(&self, x: i32, y: i32) -> Option<&Memory> {
+        self.memories[x as usize][y as usize].as_ref()
+    }
+
+    pub fn light_level(&self, x: i32, y: i32) -> Color {
+        self.light_map.level(x, y)
+    }
+
+    pub fn tiles(&self) -> impl Iterator<Item = &Tile> {
+        self.tiles.iter().flat_map(|column| column.iter())
+    }
+}
+
+impl Index<usize> for Map {
+    type Output = [Tile];
+
+    fn index(&self, x: usize) -> &[Tile] {
+        &self.tiles[x]
+    }
+}
 
 #[derive(Clone, Copy, Debug)]
-struct Rect {
-    x1: i32,
-    y1: i32,
-    x2: i32,
-    y2: i32,
+pub struct Rect {
+    pub x1: i32,
+    pub y1: i32,
+    pub x2: i32,
+    pub y2: i32,
 }
 
 impl Rect {
@@ -65,117 +287,479 @@ impl Rect {
     }
 }
 
-pub fn generate_map(elements: &mut Vec<Element>) -> (Map, (i32, i32)) {
-    let mut map = vec![vec![Tile::wall(); MAP_HEIGHT as usize]; MAP_WIDTH as usize];
+/// The different algorithms which can be used to lay out a level
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GeneratorKind {
+    /// Rectangular rooms linked by L-shaped corridors
+    Rooms,
+    /// Organic caverns grown through a cellular automaton
+    Caves,
+}
+
+impl GeneratorKind {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "rooms" => Some(GeneratorKind::Rooms),
+            "caves" => Some(GeneratorKind::Caves),
+            _ => None,
+        }
+    }
+}
+
+pub struct GeneratedMap {
+    pub map: Map,
+    pub starting_position: (i32, i32),
+    /// rooms carved while generating the map, empty for caves
+    pub rooms: Vec<Rect>,
+}
+
+pub fn generate_map(kind: GeneratorKind,
+                    rng: &mut GameRng,
+                    elements: &mut Vec<Element>) -> GeneratedMap {
+    match kind {
+        GeneratorKind::Rooms => generate_rooms(rng, elements),
+        GeneratorKind::Caves => generate_caves(rng, elements),
+    }
+}
+
+fn generate_rooms(rng: &mut GameRng, elements: &mut Vec<Element>) -> GeneratedMap {
+    let mut map = Map::new(MAP_WIDTH, MAP_HEIGHT);
+    let vaults = load_vaults().expect("invalid vaults.txt");
 
     let mut starting_position = (0, 0);
     let mut rooms: Vec<Rect> = vec![];
+    // the stairs go there, vaults keeping their centre as designed
+    let mut last_plain_room = None;
+    // what the vaults put on each tile, see `mark_vault`
+    let mut vault_cells = vec![vec![VaultCell::Untouched; MAP_HEIGHT as usize]; MAP_WIDTH as usize];
     for _ in 0..MAX_ROOMS {
-        let w = rand::thread_rng().gen_range(ROOM_MIN_SIZE, ROOM_MAX_SIZE + 1);
-        let h = rand::thread_rng().gen_range(ROOM_MIN_SIZE, ROOM_MAX_SIZE + 1);
-        let x = rand::thread_rng().gen_range(0, MAP_WIDTH - w);
-        let y = rand::thread_rng().gen_range(0, MAP_HEIGHT - h);
+        let vault = if !vaults.is_empty() && rng.gen::<f32>() < VAULT_CHANCE {
+            Some(random_vault_orientation(&vaults[rng.gen_range(0, vaults.len())], rng))
+        } else {
+            None
+        };
+
+        // like rooms, vaults' outer rows and columns are on the Rect's edges
+        let (w, h) = match vault {
+            Some(ref vault) => (vault.width() - 1, vault.height() - 1),
+            None => (rng.gen_range(ROOM_MIN_SIZE, ROOM_MAX_SIZE + 1),
+                     rng.gen_range(ROOM_MIN_SIZE, ROOM_MAX_SIZE + 1)),
+        };
+        if w >= MAP_WIDTH || h >= MAP_HEIGHT {
+            continue;
+        }
+        let x = rng.gen_range(0, MAP_WIDTH - w);
+        let y = rng.gen_range(0, MAP_HEIGHT - h);
 
         let new_room = Rect::new(x, y, w, h);
 
         // run through the other rooms and see if they intersect with this one
-        let failed = rooms.iter().any(|other_room| new_room.intersects_with(other_room));
-        if !failed {
-            create_room(new_room, &mut map);
-            place_elements(&map, new_room, elements);
-            let (new_room_x, new_room_y) = new_room.center();
-            if rooms.is_empty() {
-                starting_position = (new_room_x, new_room_y);
-            } else {
-                let (prev_room_x, prev_room_y) = rooms[rooms.len() - 1].center();
-
-                if rand::random() {
-                    create_h_tunnel(prev_room_x, new_room_x, prev_room_y, &mut map);
-                    create_v_tunnel(prev_room_y, new_room_y, new_room_x, &mut map);
-                } else {
-                    create_v_tunnel(prev_room_y, new_room_y, prev_room_x, &mut map);
-                    create_h_tunnel(prev_room_x, new_room_x, new_room_y, &mut map);
+        if rooms.iter().any(|other_room| new_room.intersects_with(other_room)) {
+            continue;
+        }
+        // vaults must not wall up corridors, nor corridors cut through
+        // vaults: rooms where both are unavoidable are left out
+        let mut new_vault_cells = vault_cells.clone();
+        if let Some(ref vault) = vault {
+            if !vault_fits(vault, x, y, &map) {
+                continue;
+            }
+            mark_vault(vault, x, y, &mut new_vault_cells);
+        }
+        let new_center = new_room.center();
+        let corridor = match rooms.last() {
+            None => vec![],
+            Some(prev_room) => {
+                let horizontal_first: bool = rng.gen();
+                let clear = [horizontal_first, !horizontal_first].iter()
+                    .map(|&horizontal| corridor(prev_room.center(), new_center, horizontal))
+                    .find(|tiles| tiles.iter().all(|&(x, y)| {
+                        new_vault_cells[x as usize][y as usize] != VaultCell::Wall
+                    }));
+                match clear {
+                    Some(tiles) => tiles,
+                    None => continue,
+                }
+            }
+        };
+        vault_cells = new_vault_cells;
+
+        match vault {
+            Some(ref vault) => stamp_vault(vault, x, y, &mut map, rng, elements),
+            None => {
+                create_room(new_room, &mut map);
+                place_elements(&map, new_room, rng, elements);
+                last_plain_room = Some(new_room);
+            }
+        }
+        if rooms.is_empty() {
+            starting_position = new_center;
+        }
+        dig_corridor(&corridor, &mut map, &vault_cells);
+        rooms.push(new_room);
+    }
+
+    for room in &rooms {
+        place_doors(*room, &mut map);
+    }
+
+    if let Some(last_room) = last_plain_room {
+        let (stairs_x, stairs_y) = last_room.center();
+        map.set_terrain(stairs_x, stairs_y, Terrain::Stairs);
+    }
+
+    GeneratedMap { map, starting_position, rooms }
+}
+
+fn generate_caves(rng: &mut GameRng, elements: &mut Vec<Element>) -> GeneratedMap {
+    let mut map = Map::new(MAP_WIDTH, MAP_HEIGHT);
+
+    // start from noise, always keeping the border closed
+    for x in 1..(MAP_WIDTH - 1) {
+        for y in 1..(MAP_HEIGHT - 1) {
+            if rng.gen::<f32>() >= CAVE_INITIAL_WALL_CHANCE {
+                map.set_terrain(x, y, Terrain::Floor);
+            }
+        }
+    }
+
+    // then smooth it: a tile becomes a wall when most of its 3x3 block is
+    for _ in 0..CAVE_SMOOTHING_STEPS {
+        let previous = map.clone();
+        for x in 1..(MAP_WIDTH - 1) {
+            for y in 1..(MAP_HEIGHT - 1) {
+                let walls = count_walls_around(&previous, x, y);
+                map.set_terrain(x, y, if walls >= 5 { Terrain::Wall } else { Terrain::Floor });
+            }
+        }
+    }
+
+    // only keep the biggest cavern so the whole level can be walked through
+    let mut best_region: Vec<(i32, i32)> = vec![];
+    let mut visited = vec![vec![false; MAP_HEIGHT as usize]; MAP_WIDTH as usize];
+    for x in 0..MAP_WIDTH {
+        for y in 0..MAP_HEIGHT {
+            if !visited[x as usize][y as usize] && !map[x as usize][y as usize].block_movement() {
+                let region = flood_region(&map, (x, y));
+                for &(rx, ry) in &region {
+                    visited[rx as usize][ry as usize] = true;
+                }
+                if region.len() > best_region.len() {
+                    best_region = region;
                 }
             }
-            rooms.push(new_room);
         }
     }
+    for x in 0..MAP_WIDTH {
+        for y in 0..MAP_HEIGHT {
+            map.set_terrain(x, y, Terrain::Wall);
+        }
+    }
+    for &(x, y) in &best_region {
+        if rng.gen::<f32>() < CAVE_FUNGUS_CHANCE {
+            map.set_terrain(x, y, Terrain::GlowingFungus);
+        } else {
+            map.set_terrain(x, y, Terrain::Floor);
+        }
+    }
+
+    if best_region.is_empty() {
+        return GeneratedMap { map, starting_position: (0, 0), rooms: vec![] };
+    }
+    let starting_position = best_region[rng.gen_range(0, best_region.len())];
 
-    (map, starting_position)
+    // the stairs are as far as possible from the start
+    let (start_x, start_y) = starting_position;
+    if let Some(&(stairs_x, stairs_y)) = best_region.iter()
+        .max_by_key(|&&(x, y)| (x - start_x).abs() + (y - start_y).abs()) {
+        map.set_terrain(stairs_x, stairs_y, Terrain::Stairs);
+    }
+
+    // populate areas of the size of a room around random spots of the cavern
+    for _ in 0..(MAX_ROOMS / 2) {
+        let (x, y) = best_region[rng.gen_range(0, best_region.len())];
+        let area = Rect::new(cmp::max(0, x - ROOM_MAX_SIZE / 2),
+                             cmp::max(0, y - ROOM_MAX_SIZE / 2),
+                             ROOM_MAX_SIZE,
+                             ROOM_MAX_SIZE);
+        let area = Rect {
+            x2: cmp::min(area.x2, MAP_WIDTH - 1),
+            y2: cmp::min(area.y2, MAP_HEIGHT - 1),
+            ..area
+        };
+        place_elements(&map, area, rng, elements);
+    }
+
+    GeneratedMap { map, starting_position, rooms: vec![] }
 }
 
-pub fn explore(map: &mut Map, fov_map: &FovMap) {
-    // TODO Do on the FovMap instead
-    for y in 0..MAP_HEIGHT {
-        for x in 0..MAP_WIDTH {
-            let is_visible = fov_map.is_in_sight(x, y);
+fn count_walls_around(map: &Map, x: i32, y: i32) -> i32 {
+    let mut walls = 0;
+    for dx in -1..2 {
+        for dy in -1..2 {
+            if map[(x + dx) as usize][(y + dy) as usize].block_movement() {
+                walls += 1;
+            }
+        }
+    }
+    walls
+}
 
-            let explored = &mut map[x as usize][y as usize].explored;
-            if is_visible {
-                *explored = true;
+/// every tile that can be walked to from `start`, `start` included
+fn flood_region(map: &Map, start: (i32, i32)) -> Vec<(i32, i32)> {
+    let mut seen = vec![vec![false; MAP_HEIGHT as usize]; MAP_WIDTH as usize];
+    let mut region = vec![];
+    let mut to_visit = vec![start];
+    seen[start.0 as usize][start.1 as usize] = true;
+    while let Some((x, y)) = to_visit.pop() {
+        region.push((x, y));
+        for &(dx, dy) in &[(0, -1), (0, 1), (-1, 0), (1, 0)] {
+            let (nx, ny) = (x + dx, y + dy);
+            if nx < 0 || ny < 0 || nx >= MAP_WIDTH || ny >= MAP_HEIGHT {
+                continue;
+            }
+            if !seen[nx as usize][ny as usize] && map[nx as usize][ny as usize].is_passable() {
+                seen[nx as usize][ny as usize] = true;
+                to_visit.push((nx, ny));
             }
         }
     }
+    region
+}
+
+/// return true if every walkable tile of the map can be reached from `start`
+pub fn is_connected(map: &Map, start: (i32, i32)) -> bool {
+    let walkable = map.tiles()
+        .filter(|tile| tile.is_passable())
+        .count();
+    flood_region(map, start).len() == walkable
 }
 
 fn create_room(room: Rect, map: &mut Map) {
     for x in (room.x1 + 1)..room.x2 {
         for y in (room.y1 + 1)..room.y2 {
-            map[x as usize][y as usize] = Tile::empty();
+            map.set_terrain(x, y, Terrain::Floor);
         }
     }
 }
 
-fn create_h_tunnel(x1: i32, x2: i32, y: i32, map: &mut Map) {
-    for x in cmp::min(x1, x2)..(cmp::max(x1, x2) + 1) {
-        map[x as usize][y as usize] = Tile::empty();
+fn random_vault_orientation(vault: &Vault, rng: &mut GameRng) -> Vault {
+    let mut vault = vault.clone();
+    for _ in 0..rng.gen_range(0, 4) {
+        vault = vault.rotated();
+    }
+    if rng.gen() {
+        vault = vault.mirrored();
+    }
+    vault
+}
+
+/// whether the vault can go there without walling up the corridors already
+/// dug, see `Vault::blocks_corridors`
+fn vault_fits(vault: &Vault, x: i32, y: i32, map: &Map) -> bool {
+    (0..vault.width()).all(|vault_x| (0..vault.height()).all(|vault_y| {
+        !vault.blocks_corridors(vault_x, vault_y) ||
+            map[(x + vault_x) as usize][(y + vault_y) as usize].terrain == Terrain::Wall
+    }))
+}
+
+/// Record the vault's cells on the tiles it covers, leaving out the walls
+/// corridors may dig through.
+fn mark_vault(vault: &Vault, x: i32, y: i32, vault_cells: &mut [Vec<VaultCell>]) {
+    for vault_x in 0..vault.width() {
+        for vault_y in 0..vault.height() {
+            let cell = vault.get(vault_x, vault_y);
+            if cell != VaultCell::Untouched &&
+                (cell != VaultCell::Wall || vault.blocks_corridors(vault_x, vault_y)) {
+                vault_cells[(x + vault_x) as usize][(y + vault_y) as usize] = cell;
+            }
+        }
+    }
+}
+
+/// copy the vault on the map with its top-left corner at the given position
+fn stamp_vault(vault: &Vault,
+               x: i32,
+               y: i32,
+               map: &mut Map,
+               rng: &mut GameRng,
+               elements: &mut Vec<Element>) {
+    for vault_y in 0..vault.height() {
+        for vault_x in 0..vault.width() {
+            let (map_x, map_y) = (x + vault_x, y + vault_y);
+            let cell = vault.get(vault_x, vault_y);
+            match cell {
+                VaultCell::Untouched => continue,
+                // corridors already dug to the centre stay open
+                VaultCell::Wall if map[map_x as usize][map_y as usize].is_passable() => {}
+                VaultCell::Wall => map.set_terrain(map_x, map_y, Terrain::Wall),
+                VaultCell::Terrain(terrain) => map.set_terrain(map_x, map_y, terrain),
+                _ => map.set_terrain(map_x, map_y, Terrain::Floor),
+            }
+            match cell {
+                VaultCell::Orc => elements.push(make_orc(map_x, map_y)),
+                VaultCell::Troll => elements.push(make_troll(map_x, map_y)),
+                VaultCell::Potion => elements.push(make_potion(map_x, map_y)),
+                VaultCell::RandomSpawn => {
+                    if rng.gen() {
+                        elements.push(make_random_monster(map_x, map_y, rng));
+                    } else {
+                        elements.push(make_random_item(map_x, map_y, rng));
+                    }
+                }
+                _ => {}
+            }
+        }
     }
 }
 
-fn create_v_tunnel(y1: i32, y2: i32, x: i32, map: &mut Map) {
+/// put a closed door on each spot where a corridor goes through the room's walls
+fn place_doors(room: Rect, map: &mut Map) {
+    let is_wall = |map: &Map, x: i32, y: i32| {
+        map[x as usize][y as usize].block_movement()
+    };
+    for x in (room.x1 + 1)..room.x2 {
+        for &y in &[room.y1, room.y2] {
+            if !is_wall(map, x, y) && is_wall(map, x - 1, y) && is_wall(map, x + 1, y) {
+                map.set_terrain(x, y, Terrain::Door);
+            }
+        }
+    }
+    for y in (room.y1 + 1)..room.y2 {
+        for &x in &[room.x1, room.x2] {
+            if !is_wall(map, x, y) && is_wall(map, x, y - 1) && is_wall(map, x, y + 1) {
+                map.set_terrain(x, y, Terrain::Door);
+            }
+        }
+    }
+}
+
+/// the tiles of an L-shaped corridor between two points
+fn corridor((x1, y1): (i32, i32), (x2, y2): (i32, i32), horizontal_first: bool) -> Vec<(i32, i32)> {
+    let (corner_x, corner_y) = if horizontal_first { (x2, y1) } else { (x1, y2) };
+    let mut tiles = vec![];
+    for x in cmp::min(x1, x2)..(cmp::max(x1, x2) + 1) {
+        tiles.push((x, corner_y));
+    }
     for y in cmp::min(y1, y2)..(cmp::max(y1, y2) + 1) {
-        map[x as usize][y as usize] = Tile::empty();
+        tiles.push((corner_x, y));
+    }
+    tiles
+}
+
+/// dig the corridor, going over the vaults' cells without changing them
+fn dig_corridor(tiles: &[(i32, i32)], map: &mut Map, vault_cells: &[Vec<VaultCell>]) {
+    for &(x, y) in tiles {
+        if vault_cells[x as usize][y as usize] == VaultCell::Untouched {
+            map.set_terrain(x, y, Terrain::Floor);
+        }
     }
 }
 
-fn place_elements(map: &Map, room: Rect, elements: &mut Vec<Element>) {
-    let num_monsters =  rand::thread_rng().gen_range(0, MAX_ROOM_MONSTERS + 1);
+fn place_elements(map: &Map, room: Rect, rng: &mut GameRng, elements: &mut Vec<Element>) {
+    let num_monsters = rng.gen_range(0, MAX_ROOM_MONSTERS + 1);
 
+    let first_monster = elements.len();
     for _ in 0..num_monsters {
-        let x = rand::thread_rng().gen_range(room.x1 + 1, room.x2);
-        let y = rand::thread_rng().gen_range(room.y1 + 1, room.y2);
+        let x = rng.gen_range(room.x1 + 1, room.x2);
+        let y = rng.gen_range(room.y1 + 1, room.y2);
 
         if !is_blocked(x, y, map, elements) {
-            let monster = if rand::random::<f32>() < 0.8 {
-                make_orc(x, y)
-            } else {
-                make_troll(x, y)
-            };
-            elements.push(monster);
+            elements.push(make_random_monster(x, y, rng));
         }
     }
+    form_packs(elements, first_monster);
 
     // choose random number of items
-    let num_items = rand::thread_rng().gen_range(0, MAX_ROOM_ITEMS + 1);
+    let num_items = rng.gen_range(0, MAX_ROOM_ITEMS + 1);
 
     for _ in 0..num_items {
         // choose random spot for this item
-        let x = rand::thread_rng().gen_range(room.x1 + 1, room.x2);
-        let y = rand::thread_rng().gen_range(room.y1 + 1, room.y2);
+        let x = rng.gen_range(room.x1 + 1, room.x2);
+        let y = rng.gen_range(room.y1 + 1, room.y2);
 
         // only place it if the tile is not blocked
         if !is_blocked(x, y, map, elements) {
-            // create a healing potion
-            let element = make_potion(x, y);
-            elements.push(element);
+            elements.push(make_random_item(x, y, rng));
         }
     }
 
 
 }
 
+/// Group the monsters spawned from `first_monster` on into packs, one per
+/// faction with more than one of them, led by the first one spawned.
+fn form_packs(elements: &mut [Element], first_monster: usize) {
+    let mut next_pack = elements.iter()
+        .filter_map(|element| element.pack)
+        .map(|member| member.pack + 1)
+        .max()
+        .unwrap_or(0);
+    let monsters = &mut elements[first_monster..];
+    for first in 0..monsters.len() {
+        let faction = monsters[first].faction;
+        if faction == Faction::Neutral || monsters[first].pack.is_some() {
+            continue;
+        }
+        let members: Vec<usize> = (first..monsters.len())
+            .filter(|&id| monsters[id].faction == faction)
+            .collect();
+        if members.len() < 2 {
+            continue;
+        }
+        for &id in &members {
+            monsters[id].pack = Some(PackMember { pack: next_pack, leader: id == first });
+        }
+        next_pack += 1;
+    }
+}
+
+fn make_random_monster(x: i32, y: i32, rng: &mut GameRng) -> Element {
+    let roll = rng.gen::<f32>();
+    let mut monster = if roll < 0.4 {
+        make_orc(x, y)
+    } else if roll < 0.5 {
+        make_rat(x, y)
+    } else if roll < 0.62 {
+        make_goblin(x, y)
+    } else if roll < 0.74 {
+        make_orc_archer(x, y)
+    } else if roll < 0.82 {
+        make_orc_shaman(x, y)
+    } else if roll < 0.86 {
+        make_orc_warlord(x, y)
+    } else if roll < 0.91 {
+        make_drake(x, y)
+    } else {
+        make_troll(x, y)
+    };
+    if rng.gen::<f32>() < TORCH_BEARER_CHANCE {
+        monster.light = Some(Light { radius: MONSTER_TORCH_RADIUS, color: COLOR_TORCH });
+    }
+    monster
+}
+
+fn make_random_item(x: i32, y: i32, rng: &mut GameRng) -> Element {
+    let roll = rng.gen::<f32>();
+    if roll < 0.5 {
+        make_potion(x, y)
+    } else if roll < 0.58 {
+        make_potion_of_haste(x, y)
+    } else if roll < 0.66 {
+        make_potion_of_regeneration(x, y)
+    } else if roll < 0.74 {
+        make_potion_of_invisibility(x, y)
+    } else if roll < 0.84 {
+        make_scroll_of_confusion(x, y)
+    } else if roll < 0.92 {
+        make_scroll_of_blinding(x, y)
+    } else {
+        make_scroll_of_thunder(x, y)
+    }
+}
+
 fn is_blocked(x : i32, y: i32, map: &Map, elements: &[Element]) -> bool {
-    if map[x as usize][y as usize].block_movement {
+    if map[x as usize][y as usize].block_movement() {
         return true;
     }
 
@@ -184,9 +768,117 @@ fn is_blocked(x : i32, y: i32, map: &Map, elements: &[Element]) -> bool {
     })
 }
 
-pub fn move_by(id: usize, map: &Map, elements: &mut [Element], dx: i32, dy: i32) {
+/// The nearest tile around the given one where an element could stand,
+/// at most `max_distance` tiles away.
+pub fn free_spot_near(map: &Map,
+                      elements: &[Element],
+                      (x, y): (i32, i32),
+                      max_distance: i32) -> Option<(i32, i32)> {
+    for distance in 1..(max_distance + 1) {
+        for dx in -distance..(distance + 1) {
+            for dy in -distance..(distance + 1) {
+                let (spot_x, spot_y) = (x + dx, y + dy);
+                let on_ring = dx.abs() == distance || dy.abs() == distance;
+                if on_ring && map.is_in_bounds(spot_x, spot_y) && !is_blocked(spot_x, spot_y, map, elements) {
+                    return Some((spot_x, spot_y));
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Move the element by the given amount.
+/// Bumping into a closed door opens it instead of moving.
+pub fn move_by(id: usize, map: &mut Map, elements: &mut [Element], dx: i32, dy: i32) {
     let (x, y) = elements[id].pos();
-    if !is_blocked(x + dx, y + dy, map, elements) {
+    if map[(x + dx) as usize][(y + dy) as usize].terrain == Terrain::Door {
+        map.set_terrain(x + dx, y + dy, Terrain::OpenDoor);
+    } else if !is_blocked(x + dx, y + dy, map, elements) {
         elements[id].set_pos(x + dx, y + dy);
     }
 }
+
+/// Move like `move_by`, spending the energy walking on the new tile takes.
+/// Opening a door or bumping into something costs a normal action.
+pub fn walk_by(id: usize, map: &mut Map, elements: &mut [Element], dx: i32, dy: i32) {
+    let start = elements[id].pos();
+    move_by(id, map, elements, dx, dy);
+    let (x, y) = elements[id].pos();
+    elements[id].energy -= if (x, y) == start {
+        TURN_ENERGY
+    } else {
+        map[x as usize][y as usize].terrain.info().movement_cost * MOVE_COST
+    };
+}
+
+/// Close the door at the given position.
+/// Returns false if something stands in the doorway.
+pub fn close_door(x: i32, y: i32, map: &mut Map, elements: &[Element]) -> bool {
+    if elements.iter().any(|element| element.pos() == (x, y)) {
+        return false;
+    }
+    map.set_terrain(x, y, Terrain::Door);
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::seeded_rng;
+    use crate::vault::parse_vaults;
+
+    #[test]
+    fn corridors_only_dig_through_the_middle_of_vaults() {
+        let source = "vault pool\n#####\n#...#\n#.=.#\n#...#\n#####";
+        let vault = parse_vaults(source).unwrap().remove(0);
+        let mut map = Map::new(7, 7);
+        let mut vault_cells = vec![vec![VaultCell::Untouched; 7]; 7];
+        mark_vault(&vault, 1, 1, &mut vault_cells);
+        stamp_vault(&vault, 1, 1, &mut map, &mut seeded_rng(0), &mut vec![]);
+
+        let blocked = corridor((0, 2), (6, 2), true);
+        let is_wall = |&(x, y): &(i32, i32)| vault_cells[x as usize][y as usize] == VaultCell::Wall;
+        assert!(blocked.iter().any(is_wall));
+
+        dig_corridor(&corridor((0, 3), (6, 3), true), &mut map, &vault_cells);
+        assert_eq!(map[1][3].terrain, Terrain::Floor);
+        assert_eq!(map[3][3].terrain, Terrain::DeepWater);
+        assert_eq!(map[5][3].terrain, Terrain::Floor);
+        assert_eq!(map[1][2].terrain, Terrain::Wall);
+    }
+
+    #[test]
+    fn stairs_are_never_put_in_vaults() {
+        for seed in 0..40 {
+            let mut elements = vec![make_player(0, 0)];
+            let mut rng = seeded_rng(seed);
+            let generated = generate_map(GeneratorKind::Rooms, &mut rng, &mut elements);
+            let map = &generated.map;
+            let stairs_room = generated.rooms.iter()
+                .find(|room| {
+                    let (x, y) = room.center();
+                    map[x as usize][y as usize].terrain == Terrain::Stairs
+                })
+                .expect("no stairs in a room");
+            // unlike vaults, plain rooms are floor all over
+            let plain = ((stairs_room.x1 + 1)..stairs_room.x2).all(|x| {
+                ((stairs_room.y1 + 1)..stairs_room.y2).all(|y| {
+                    let terrain = map[x as usize][y as usize].terrain;
+                    terrain == Terrain::Floor || terrain == Terrain::Stairs
+                })
+            });
+            assert!(plain, "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn generated_levels_are_connected() {
+        for seed in 0..20 {
+            let mut elements = vec![make_player(0, 0)];
+            let mut rng = seeded_rng(seed);
+            let generated = generate_map(GeneratorKind::Rooms, &mut rng, &mut elements);
+            assert!(is_connected(&generated.map, generated.starting_position), "seed {}", seed);
+        }
+    }
+}
@@ -0,0 +1,194 @@
+//! A* pathfinding over the map, for monsters walking towards a target.
+use crate::element::Element;
+use crate::map::Map;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+/// extra cost of walking through a tile another element stands on, so paths
+/// go around it when they can but still go through it when they cannot
+const OCCUPIED_COST: i32 = 10;
+
+pub const ORTHOGONAL_MOVES : [(i32, i32); 4] = [(0, -1), (1, 0), (0, 1), (-1, 0)];
+pub const DIAGONAL_MOVES : [(i32, i32); 4] = [(-1, -1), (1, -1), (1, 1), (-1, 1)];
+
+/// Cost of stepping on the given tile, None if it cannot be walked on.
+fn step_cost(x: i32, y: i32, map: &Map, elements: &[Element], mover_id: usize) -> Option<i32> {
+    let mut cost = map[x as usize][y as usize].movement_cost()?;
+    let occupied = elements.iter().enumerate().any(|(id, element)| {
+        id != mover_id && element.block_movement && element.pos() == (x, y)
+    });
+    if occupied {
+        cost += OCCUPIED_COST;
+    }
+    Some(cost)
+}
+
+/// Find the cheapest path for an element to the given target, along the
+/// terrain's movement costs.
+/// The target itself can be occupied, e.g. by the element being chased.
+/// Diagonal moves are only allowed when they do not cut a wall's corner.
+/// Returns the steps to take, the starting position excluded, or None if
+/// the target cannot be reached.
+pub fn find_path(mover_id: usize,
+                 target: (i32, i32),
+                 map: &Map,
+                 elements: &[Element],
+                 diagonals: bool) -> Option<Vec<(i32, i32)>> {
+    let (width, height) = (map.width(), map.height());
+    let index = |(x, y): (i32, i32)| (x * height + y) as usize;
+    let heuristic = |(x, y): (i32, i32)| {
+        let (dx, dy) = ((x - target.0).abs(), (y - target.1).abs());
+        if diagonals { dx.max(dy) } else { dx + dy }
+    };
+    let start = elements[mover_id].pos();
+
+    let mut costs = vec![i32::MAX; (width * height) as usize];
+    let mut came_from = vec![None; (width * height) as usize];
+    let mut open = BinaryHeap::new();
+    costs[index(start)] = 0;
+    open.push(Reverse((heuristic(start), start)));
+
+    while let Some(Reverse((priority, current))) = open.pop() {
+        if priority > costs[index(current)] + heuristic(current) {
+            // a cheaper way to this position was already found
+            continue;
+        }
+        if current == target {
+            let mut path = vec![current];
+            let mut position = current;
+            while let Some(previous) = came_from[index(position)] {
+                if previous != start {
+                    path.push(previous);
+                }
+                position = previous;
+            }
+            path.reverse();
+            return Some(path);
+        }
+
+        let moves = ORTHOGONAL_MOVES.iter()
+            .chain(DIAGONAL_MOVES.iter().filter(|_| diagonals));
+        for &(dx, dy) in moves {
+            let next = (current.0 + dx, current.1 + dy);
+            if !map.is_in_bounds(next.0, next.1) || map.cuts_corner(current.0, current.1, dx, dy) {
+                continue;
+            }
+            let cost = if next == target {
+                // the target may stand there, only the terrain matters
+                map[next.0 as usize][next.1 as usize].movement_cost()
+            } else {
+                step_cost(next.0, next.1, map, elements, mover_id)
+            };
+            if let Some(cost) = cost {
+                let next_cost = costs[index(current)] + cost;
+                if next_cost < costs[index(next)] {
+                    costs[index(next)] = next_cost;
+                    came_from[index(next)] = Some(current);
+                    open.push(Reverse((next_cost + heuristic(next), next)));
+                }
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::element::make_orc;
+    use crate::terrain::Terrain;
+
+    /// `#` are walls, `@` the mover, `o` another monster and `T` the target
+    fn find(rows: &[&str], diagonals: bool) -> Option<Vec<(i32, i32)>> {
+        let mut map = Map::new(rows[0].len() as i32, rows.len() as i32);
+        let mut elements = vec![];
+        let mut target = (0, 0);
+        for (y, row) in rows.iter().enumerate() {
+            for (x, c) in row.chars().enumerate() {
+                let (x, y) = (x as i32, y as i32);
+                if c == '#' {
+                    continue;
+                }
+                map.set_terrain(x, y, Terrain::Floor);
+                match c {
+                    '@' => elements.insert(0, make_orc(x, y)),
+                    'o' => elements.push(make_orc(x, y)),
+                    'T' => target = (x, y),
+                    _ => {}
+                }
+            }
+        }
+        find_path(0, target, &map, &elements, diagonals)
+    }
+
+    #[test]
+    fn paths_go_around_wall_corners() {
+        let rows = [
+            "######",
+            "#@...#",
+            "###..#",
+            "#T...#",
+            "######",
+        ];
+        let path = find(&rows, true).unwrap();
+        assert_eq!(path.len(), 6);
+        assert_eq!(path.last(), Some(&(1, 3)));
+        // neither diagonal step past the wall's end is taken
+        let cut = |steps: &[(i32, i32)]| steps == [(2, 1), (3, 2)] || steps == [(3, 2), (2, 3)];
+        assert!(!path.windows(2).any(cut));
+    }
+
+    #[test]
+    fn paths_go_around_other_elements_when_they_can() {
+        let rows = [
+            "#######",
+            "#.....#",
+            "#@.o.T#",
+            "#######",
+        ];
+        let path = find(&rows, false).unwrap();
+        assert_eq!(path.len(), 6);
+        assert!(!path.contains(&(3, 2)));
+
+        let rows = [
+            "#######",
+            "#@.o.T#",
+            "#######",
+        ];
+        assert_eq!(find(&rows, false), Some(vec![(2, 1), (3, 1), (4, 1), (5, 1)]));
+    }
+
+    #[test]
+    fn diagonal_moves_can_be_disabled() {
+        let rows = [
+            "#####",
+            "#@..#",
+            "#...#",
+            "#..T#",
+            "#####",
+        ];
+        assert_eq!(find(&rows, true), Some(vec![(2, 2), (3, 3)]));
+        assert_eq!(find(&rows, false).map(|path| path.len()), Some(4));
+    }
+
+    #[test]
+    fn diagonal_moves_do_not_cut_corners() {
+        let rows = [
+            "####",
+            "#@.#",
+            "##T#",
+            "####",
+        ];
+        assert_eq!(find(&rows, true), Some(vec![(2, 1), (2, 2)]));
+    }
+
+    #[test]
+    fn unreachable_targets_have_no_path() {
+        let rows = [
+            "#######",
+            "#@.#.T#",
+            "#######",
+        ];
+        assert_eq!(find(&rows, true), None);
+    }
+}
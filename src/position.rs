@@ -6,7 +6,7 @@ pub struct Position {
 
 impl Position {
     pub fn new(x: i32, y: i32) -> Self {
-        Position { x: x, y: y}
+        Position { x, y }
     }
 
     pub fn distance_to(&self, other_pos: &Position) -> f32 {
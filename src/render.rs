@@ -15,7 +15,9 @@ use crate::constants::{
 };
 use crate::{
     state::GameState,
-    element::Element,
+    element::{Element, capitalize},
+    light::shade,
+    status,
 };
 use tcod::colors::{self, Color};
 use tcod::console::*;
@@ -24,20 +26,50 @@ pub fn render_all(
     root: &mut Root,
     con: &mut Offscreen,
     panel: &mut Offscreen,
-    hovered: &Vec<String>,
+    hovered: &[String],
     game_state: &GameState
 ) {
     con.set_default_foreground(colors::WHITE);
     con.clear();
 
+    for y in 0..MAP_HEIGHT {
+        for x in 0..MAP_WIDTH {
+            if game_state.map.is_explored(x, y) {
+                let is_visible = game_state.map.is_in_sight(x, y);
+                let terrain = game_state.map[x as usize][y as usize].terrain.info();
+                let (background, glyph_color) = if is_visible {
+                    let light = game_state.map.light_level(x, y);
+                    (shade(terrain.lit_color, terrain.dark_color, light), terrain.glyph_color)
+                } else {
+                    (terrain.dark_color, terrain.glyph_color * 0.5)
+                };
+                con.set_char_background(x, y, background, BackgroundFlag::Set);
+                if terrain.glyph != ' ' {
+                    con.set_default_foreground(glyph_color);
+                    con.put_char(x, y, terrain.glyph, BackgroundFlag::None);
+                }
+                // what was last seen there, dimmed
+                if !is_visible {
+                    if let Some(memory) = game_state.map.memory(x, y) {
+                        con.set_default_foreground(memory.color * 0.5);
+                        con.put_char(x, y, memory.char, BackgroundFlag::None);
+                    }
+                }
+            }
+        }
+    }
+
     let mut to_draw: Vec<_> = game_state.elements
         .iter()
-        .filter(|e| game_state.fov_map.is_in_sight(e.position.x, e.position.y))
+        .filter(|e| {
+            game_state.map.is_in_sight(e.position.x, e.position.y) &&
+                status::is_seen_by(e, &game_state.elements[PLAYER])
+        })
         .collect();
 
     // sort so that non-blocknig objects come first
     to_draw.sort_by(|e1, e2| { e1.block_movement.cmp(&e2.block_movement) });
-    // draw the objects in the list
+    // draw the objects in the list, over the terrain they stand on
     for element in &to_draw {
         con.set_default_foreground(element.color);
         con.put_char(element.position.x,
@@ -46,22 +78,6 @@ pub fn render_all(
                      BackgroundFlag::None);
     }
 
-    for y in 0..MAP_HEIGHT {
-        for x in 0..MAP_WIDTH {
-            let explored = game_state.map[x as usize][y as usize].explored;
-            if explored {
-                let is_visible = game_state.fov_map.is_in_sight(x, y);
-                let is_wall = game_state.map[x as usize][y as usize].block_sight;
-                let color = match (is_visible, is_wall) {
-                    (false, true) => COLOR_DARK_WALL,
-                    (false, false) => COLOR_DARK_GROUND,
-                    (true, true) => COLOR_LIGHT_WALL,
-                    (true, false) => COLOR_LIGHT_GROUND,
-                };
-                con.set_char_background(x, y, color, BackgroundFlag::Set);
-            }
-        }
-    }
     blit(con, (0, 0), (MAP_WIDTH, MAP_HEIGHT), root, (0, 0), 1.0, 1.0);
 
     panel.set_default_background(colors::BLACK);
@@ -70,6 +86,21 @@ pub fn render_all(
     let hp = game_state.elements[PLAYER].fighter.map_or(0, |f| f.hp);
     let max_hp = game_state.elements[PLAYER].fighter.map_or(0, |f| f.max_hp);
     render_bar(panel, 1, 1, BAR_WIDTH, "HP", hp, max_hp, COLOR_HP_FOREGROUND, COLOR_HP_BACKGROUND);
+    let xp = game_state.elements[PLAYER].xp;
+    let level = format!("Level {}", game_state.elements[PLAYER].level);
+    render_bar(panel, 1, 2, BAR_WIDTH, &level, xp, game_state.xp_to_level_up(),
+               COLOR_XP_FOREGROUND, COLOR_XP_BACKGROUND);
+
+    panel.set_default_foreground(colors::LIGHT_GREY);
+    panel.print_ex(1, 3, BackgroundFlag::None, TextAlignment::Left,
+                   format!("Dungeon level: {}", game_state.dungeon_level));
+
+    // the player's statuses below, as many as fit
+    for (y, status) in (4..PANEL_HEIGHT).zip(game_state.elements[PLAYER].statuses.iter()) {
+        panel.set_default_foreground(status.kind.color());
+        panel.print_ex(1, y, BackgroundFlag::None, TextAlignment::Left,
+                       format!("{} ({})", capitalize(status.kind.name()), status.turns));
+    }
 
     // print the game messages, one line at a time
     let mut y = MSG_HEIGHT as i32;
@@ -94,7 +125,7 @@ pub fn render_all(
 
 pub fn inventory_menu(inventory: &[Element], header: &str, root: &mut Root) -> Option<usize> {
     // how a menu with each item of the inventory as an option
-    let options = if inventory.len() == 0 {
+    let options = if inventory.is_empty() {
         vec!["Inventory is empty.".into()]
     } else {
         inventory.iter().map(|item| { item.display_name.clone() }).collect()
@@ -103,14 +134,14 @@ pub fn inventory_menu(inventory: &[Element], header: &str, root: &mut Root) -> O
     let inventory_index = menu(header, &options, INVENTORY_WIDTH, root);
 
     // if an item was chosen, return it
-    if inventory.len() > 0 {
+    if !inventory.is_empty() {
         inventory_index
     } else {
         None
     }
 }
 
-fn menu<T: AsRef<str>>(header: &str, options: &[T], width: i32,
+pub fn menu<T: AsRef<str>>(header: &str, options: &[T], width: i32,
                            root: &mut Root) -> Option<usize> {
     assert!(options.len() <= 26, "Cannot have a menu with more than 26 options.");
 
@@ -137,7 +168,7 @@ fn menu<T: AsRef<str>>(header: &str, options: &[T], width: i32,
     // blit the contents of "window" to the root console
     let x = SCREEN_WIDTH / 2 - width / 2;
     let y = SCREEN_HEIGHT / 2 - height / 2;
-    tcod::console::blit(&mut window, (0, 0), (width, height), root, (x, y), 1.0, 0.7);
+    tcod::console::blit(&window, (0, 0), (width, height), root, (x, y), 1.0, 0.7);
 
     // present the root console to the player and wait for a key-press
     root.flush();
@@ -156,6 +187,7 @@ fn menu<T: AsRef<str>>(header: &str, options: &[T], width: i32,
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn render_bar(panel: &mut Offscreen,
               x: i32,
               y: i32,
@@ -182,5 +214,5 @@ fn render_bar(panel: &mut Offscreen,
     // finally, some centered text with the values
     panel.set_default_foreground(colors::WHITE);
     panel.print_ex(x + total_width / 2, y, BackgroundFlag::None, TextAlignment::Center,
-                   &format!("{}: {}/{}", name, value, maximum));
+                   format!("{}: {}/{}", name, value, maximum));
 }
@@ -0,0 +1,211 @@
+//! Who acts when: every living element gains energy as time passes, at its
+//! own speed, and acts once it has `TURN_ENERGY`. Actions spend energy, so
+//! fast elements act more often and costly actions leave more time to others.
+use crate::ai::ai_take_turn;
+use crate::constants::{PLAYER, REGEN_TURNS, TICKS_PER_TURN, TURN_ENERGY};
+use crate::effects;
+use crate::state::GameState;
+use crate::status::{self, StatusKind};
+use crate::travel;
+
+/// Let time pass until the player can act again, monsters acting whenever
+/// they have enough energy in the meantime.
+pub fn pass_time(game_state: &mut GameState) {
+    let hp = game_state.elements[PLAYER].fighter.map_or(0, |f| f.hp);
+    game_state.update_fov();
+    hurt_by_terrain(PLAYER, game_state);
+    game_state.update_ai_maps();
+    while game_state.elements[PLAYER].alive && game_state.elements[PLAYER].energy < TURN_ENERGY {
+        for element in game_state.elements.iter_mut().filter(|element| element.alive) {
+            element.energy += status::speed(element) / TICKS_PER_TURN;
+        }
+        game_state.ticks += 1;
+        if game_state.ticks.is_multiple_of(TICKS_PER_TURN as u32) {
+            end_turn(game_state);
+        }
+        for id in 0..game_state.elements.len() {
+            while game_state.elements[id].ai.is_some() &&
+                game_state.elements[id].energy >= TURN_ENERGY &&
+                game_state.elements[PLAYER].alive {
+                ai_take_turn(id, game_state);
+                hurt_by_terrain(id, game_state);
+            }
+        }
+    }
+    // monsters may have opened doors
+    game_state.update_fov();
+    if game_state.elements[PLAYER].fighter.map_or(0, |f| f.hp) < hp {
+        travel::interrupt(game_state, "you are hurt");
+    }
+}
+
+/// Things happening once per turn, whoever acts during it.
+fn end_turn(game_state: &mut GameState) {
+    if game_state.ticks.is_multiple_of(REGEN_TURNS * TICKS_PER_TURN as u32) {
+        for element in game_state.elements.iter_mut().filter(|element| element.alive) {
+            if !status::has(element, StatusKind::Poisoned) {
+                element.heal(1);
+            }
+        }
+    }
+    for id in 0..game_state.elements.len() {
+        if game_state.elements[id].alive && !game_state.elements[id].statuses.is_empty() {
+            status::tick(id, game_state);
+        }
+    }
+}
+
+/// Hurt an element which just acted while standing on harmful terrain,
+/// such as lava.
+fn hurt_by_terrain(id: usize, game_state: &mut GameState) {
+    let (x, y) = game_state.elements[id].pos();
+    let info = game_state.map[x as usize][y as usize].terrain.info();
+    let (amount, damage_type) = match info.damage {
+        Some(damage) => damage,
+        None => return,
+    };
+    let hurt = game_state.elements[id].alive &&
+        game_state.elements[id].fighter.is_some_and(|f| f.damage_taken(amount, damage_type) > 0);
+    if !hurt {
+        return;
+    }
+    let mut unseen_log = vec![];
+    let log = if game_state.map.is_in_sight(x, y) { &mut game_state.log } else { &mut unseen_log };
+    let xp = effects::damage(&mut game_state.elements[id], info.name, amount, damage_type, log);
+    effects::reward_player(&mut game_state.elements, id, xp, log);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::NORMAL_SPEED;
+    use crate::element::{Ai, make_drake, make_orc, make_player};
+    use crate::map::Map;
+    use crate::terrain::Terrain;
+    use crate::travel::AutoMove;
+    use crate::utils::seeded_rng;
+
+    /// a player far from wandering orcs of the given speeds, in an open room
+    fn fixture(speeds: &[i32]) -> GameState {
+        let mut map = Map::new(30, 10);
+        for x in 1..29 {
+            for y in 1..9 {
+                map.set_terrain(x, y, Terrain::Floor);
+            }
+        }
+        let mut elements = vec![make_player(1, 1)];
+        for (i, &speed) in speeds.iter().enumerate() {
+            let mut orc = make_orc(20 + i as i32, 5);
+            orc.ai = Some(Ai::Wandering);
+            orc.sight_radius = 0;
+            orc.speed = speed;
+            elements.push(orc);
+        }
+        GameState::new(map, elements, seeded_rng(0))
+    }
+
+    /// the player waits, then everybody else acts until it is their turn
+    fn player_waits(game_state: &mut GameState) {
+        game_state.elements[PLAYER].energy -= TURN_ENERGY;
+        pass_time(game_state);
+    }
+
+    #[test]
+    fn monsters_act_according_to_their_speed() {
+        let mut game_state = fixture(&[NORMAL_SPEED, 2 * NORMAL_SPEED, NORMAL_SPEED / 2]);
+        let mut actions = [0; 3];
+        for _ in 0..4 {
+            let energies: Vec<_> = game_state.elements[1..].iter().map(|e| e.energy).collect();
+            player_waits(&mut game_state);
+            // the player waited for a whole turn, and every action of a
+            // wandering monster costs a turn on the floor
+            for (i, element) in game_state.elements[1..].iter().enumerate() {
+                actions[i] += (energies[i] + element.speed - element.energy) / TURN_ENERGY;
+            }
+        }
+        assert_eq!(actions, [4, 8, 2]);
+    }
+
+    #[test]
+    fn cheap_actions_leave_less_time_to_others() {
+        let mut game_state = fixture(&[NORMAL_SPEED]);
+        game_state.elements[PLAYER].energy -= TURN_ENERGY / 2;
+        pass_time(&mut game_state);
+        assert_eq!(game_state.elements[1].energy, NORMAL_SPEED / 2);
+
+        game_state.elements[PLAYER].energy -= TURN_ENERGY / 2;
+        pass_time(&mut game_state);
+        // a single action in a turn
+        assert_eq!(game_state.elements[1].energy, 0);
+    }
+
+    #[test]
+    fn lava_burns_those_not_immune_to_fire() {
+        let mut game_state = fixture(&[]);
+        game_state.map.set_terrain(1, 1, Terrain::Lava);
+        game_state.map.set_terrain(20, 5, Terrain::Lava);
+        let mut drake = make_drake(20, 5);
+        drake.sight_radius = 0;
+        game_state.elements.push(drake);
+        player_waits(&mut game_state);
+        assert_eq!(game_state.elements[PLAYER].fighter.map(|f| f.hp), Some(24));
+        assert_eq!(game_state.log.last().map(|(message, _)| message.as_str()),
+                   Some("The lava hits you for 6 hit points."));
+        assert_eq!(game_state.elements[1].fighter.map(|f| f.hp), Some(14));
+    }
+
+    #[test]
+    fn enemies_dying_in_lava_are_worth_their_experience() {
+        let mut game_state = fixture(&[NORMAL_SPEED]);
+        game_state.map.set_terrain(20, 5, Terrain::Lava);
+        game_state.elements[1].fighter.as_mut().unwrap().hp = 1;
+        game_state.elements[1].ai = Some(Ai::Sleeping);
+        player_waits(&mut game_state);
+        assert!(!game_state.elements[1].alive);
+        assert_eq!(game_state.elements[PLAYER].xp, 35);
+    }
+
+    #[test]
+    fn getting_hurt_stops_automatic_moves() {
+        let mut game_state = fixture(&[]);
+        game_state.map.set_terrain(1, 1, Terrain::Lava);
+        game_state.auto_move = Some(AutoMove::Explore);
+        player_waits(&mut game_state);
+        assert_eq!(game_state.auto_move, None);
+        assert_eq!(game_state.log.last().map(|(message, _)| message.as_str()),
+                   Some("You stop: you are hurt."));
+    }
+
+    #[test]
+    fn statuses_wear_off_once_per_turn_whatever_the_speed() {
+        let mut game_state = fixture(&[]);
+        let player = &mut game_state.elements[PLAYER];
+        status::afflict(player, StatusKind::Hasted, 2, 0, &mut vec![]);
+        status::afflict(player, StatusKind::Poisoned, 2, 3, &mut vec![]);
+        // hasted, the player acts twice a turn
+        player_waits(&mut game_state);
+        player_waits(&mut game_state);
+        assert_eq!(game_state.elements[PLAYER].fighter.map(|f| f.hp), Some(27));
+        player_waits(&mut game_state);
+        player_waits(&mut game_state);
+        assert_eq!(game_state.elements[PLAYER].fighter.map(|f| f.hp), Some(24));
+        assert!(game_state.elements[PLAYER].statuses.is_empty());
+    }
+
+    #[test]
+    fn fighters_slowly_heal_unless_poisoned() {
+        let mut game_state = fixture(&[]);
+        game_state.elements[PLAYER].fighter.as_mut().unwrap().hp = 10;
+        for _ in 0..REGEN_TURNS {
+            player_waits(&mut game_state);
+        }
+        assert_eq!(game_state.elements[PLAYER].fighter.map(|f| f.hp), Some(11));
+
+        status::afflict(&mut game_state.elements[PLAYER], StatusKind::Poisoned, REGEN_TURNS as i32, 0,
+                       &mut vec![]);
+        for _ in 0..REGEN_TURNS {
+            player_waits(&mut game_state);
+        }
+        assert_eq!(game_state.elements[PLAYER].fighter.map(|f| f.hp), Some(11));
+    }
+}
@@ -1,43 +1,235 @@
 use crate::{
     colors,
-    constants::{MAP_HEIGHT, MAP_WIDTH, PLAYER},
-    element::{Element, make_player},
-    fov::FovMap,
-    map::{Map, generate_map},
-    messages::{Messages,MessageLog},
+    constants::{
+        BLINDED_SIGHT_RADIUS,
+        FOLLOW_DOWN_DISTANCE,
+        LEVEL_UP_BASE,
+        LEVEL_UP_FACTOR,
+        LEVEL_UP_HP,
+        MONSTER_DIAGONAL_MOVES,
+        PLAYER,
+        SIGHT_RADIUS,
+    },
+    dijkstra::DijkstraMap,
+    element::{Element, make_dog, make_player},
+    faction::Faction,
+    map::{GeneratorKind, Map, free_spot_near, generate_map},
+    messages::{Messages, MessageLog},
+    status::{self, StatusKind},
+    travel::AutoMove,
+    utils::{GameRng, seeded_rng},
 };
+use std::cmp;
+
+/// What the player can raise when reaching a new level
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StatBoost {
+    MaxHp,
+    Strength,
+    Defense,
+}
 
 pub struct GameState {
     pub elements: Vec<Element>,
     pub map: Map,
     pub inventory: Vec<Element>,
-    pub fov_map: FovMap,
     pub log: Messages,
+    pub rng: GameRng,
+    /// how deep the player went, starting at 1
+    pub dungeon_level: u32,
+    /// where the player is walking to on their own, if anywhere
+    pub auto_move: Option<AutoMove>,
+    /// leads monsters to the player, see `update_ai_maps`
+    pub player_map: DijkstraMap,
+    /// leads monsters away from the player, see `update_ai_maps`
+    pub flee_map: DijkstraMap,
+    /// time passed since the game started, see `scheduler`
+    pub ticks: u32,
+}
+
+impl GameState {
+    /// A game on the given map, before any message is logged.
+    /// The player must be the element at index `PLAYER`.
+    pub fn new(map: Map, elements: Vec<Element>, rng: GameRng) -> Self {
+        let player_map = DijkstraMap::new(map.width(), map.height(), MONSTER_DIAGONAL_MOVES);
+        GameState {
+            elements,
+            map,
+            inventory: vec![],
+            log: vec![],
+            rng,
+            dungeon_level: 1,
+            auto_move: None,
+            flee_map: player_map.clone(),
+            player_map,
+            ticks: 0,
+        }
+    }
+
+    /// Recompute what the player sees, which is cheap if neither the player,
+    /// the lights nor the terrain changed since the last call, then
+    /// remember the elements in sight.
+    pub fn update_fov(&mut self) {
+        let lights: Vec<_> = self.elements.iter()
+            .filter_map(|element| element.light.map(|light| (element.position.x, element.position.y, light)))
+            .collect();
+        let (x, y) = self.elements[PLAYER].pos();
+        let radius = if status::has(&self.elements[PLAYER], StatusKind::Blinded) {
+            BLINDED_SIGHT_RADIUS
+        } else {
+            SIGHT_RADIUS
+        };
+        self.map.update_fov(x, y, radius, &lights);
+        self.map.remember(&self.elements);
+    }
+
+    /// Go down the stairs to a new level, along with the allies close
+    /// enough to follow.
+    pub fn descend(&mut self) {
+        let player = &self.elements[PLAYER];
+        let following: Vec<usize> = self.elements.iter()
+            .enumerate()
+            .filter(|&(id, element)| {
+                id != PLAYER &&
+                    element.alive &&
+                    element.ai.is_some() &&
+                    element.faction == Faction::Player &&
+                    element.distance_to(player) <= FOLLOW_DOWN_DISTANCE
+            })
+            .map(|(id, _)| id)
+            .collect();
+        let mut elements = vec![];
+        let mut followers = vec![];
+        for (id, element) in self.elements.drain(..).enumerate() {
+            if id == PLAYER {
+                elements.push(element);
+            } else if following.contains(&id) {
+                followers.push(element);
+            }
+        }
+
+        let generated = generate_map(GeneratorKind::Rooms, &mut self.rng, &mut elements);
+        let start = generated.starting_position;
+        elements[PLAYER].set_pos(start.0, start.1);
+        let left_behind = place_near(&generated.map, &mut elements, start, followers);
+
+        self.map = generated.map;
+        self.elements = elements;
+        self.dungeon_level += 1;
+        self.auto_move = None;
+        self.player_map = DijkstraMap::new(self.map.width(), self.map.height(), MONSTER_DIAGONAL_MOVES);
+        self.flee_map = self.player_map.clone();
+        self.log.add("You descend deeper into the dungeon.", colors::COLOR_STAIRS);
+        for ally in left_behind {
+            self.log.add(format!("There is no room for your {} to follow you.", ally.display_name),
+                         tcod::colors::ORANGE);
+        }
+        self.update_fov();
+    }
+
+    /// experience the player needs to reach their next level
+    pub fn xp_to_level_up(&self) -> i32 {
+        LEVEL_UP_BASE + self.elements[PLAYER].level * LEVEL_UP_FACTOR
+    }
+
+    pub fn can_level_up(&self) -> bool {
+        self.elements[PLAYER].xp >= self.xp_to_level_up()
+    }
+
+    /// Reach the next level, spending the experience it took, and raise
+    /// the chosen stat.
+    pub fn level_up(&mut self, boost: StatBoost) {
+        let needed = self.xp_to_level_up();
+        let player = &mut self.elements[PLAYER];
+        player.level += 1;
+        player.xp -= needed;
+        if let Some(fighter) = player.fighter.as_mut() {
+            match boost {
+                StatBoost::MaxHp => {
+                    fighter.max_hp += LEVEL_UP_HP;
+                    fighter.hp += LEVEL_UP_HP;
+                }
+                StatBoost::Strength => fighter.damage.bonus += 1,
+                StatBoost::Defense => fighter.defense += 1,
+            }
+        }
+        let message = format!("Your battle skills grow stronger! You reached level {}!", player.level);
+        self.log.add(message, colors::COLOR_LEVEL_UP);
+    }
+
+    /// Recompute the maps monsters walk on, once per turn before they act.
+    pub fn update_ai_maps(&mut self) {
+        self.player_map.compute(&self.map, &[self.elements[PLAYER].pos()]);
+        self.flee_map = self.player_map.fleeing(&self.map);
+    }
+}
+
+/// Put the given elements on the free tiles nearest to the position.
+/// Returns those there is no room for anywhere on the map.
+fn place_near(map: &Map,
+              elements: &mut Vec<Element>,
+              position: (i32, i32),
+              to_place: Vec<Element>) -> Vec<Element> {
+    let max_distance = cmp::max(map.width(), map.height());
+    let mut left_out = vec![];
+    for mut element in to_place {
+        match free_spot_near(map, elements, position, max_distance) {
+            Some((x, y)) => {
+                element.set_pos(x, y);
+                elements.push(element);
+            }
+            None => left_out.push(element),
+        }
+    }
+    left_out
 }
 
-pub fn new_game() -> GameState {
+pub fn new_game(seed: usize) -> GameState {
     let player = make_player(0, 0);
     let mut elements: Vec<Element> = vec![player];
-    let inventory : Vec<Element> = vec![];
-    let mut log : Messages = vec![];
+    let mut rng = seeded_rng(seed);
 
-    let (map, starting_position) = generate_map(&mut elements);
+    let generated = generate_map(GeneratorKind::Rooms, &mut rng, &mut elements);
+    let starting_position = generated.starting_position;
     elements[PLAYER].set_pos(starting_position.0, starting_position.1);
+    // a loyal companion
+    place_near(&generated.map, &mut elements, starting_position, vec![make_dog(0, 0)]);
 
-    let mut fov_map = FovMap::new(MAP_WIDTH, MAP_HEIGHT);
-    for y in 0..MAP_HEIGHT {
-        for x in 0..MAP_WIDTH {
-            fov_map.set(x, y, map[x as usize][y as usize].block_sight);
-        }
+    let mut game_state = GameState::new(generated.map, elements, rng);
+    game_state.log.add("Welcome stranger! Prepare to perish in the Tombs of the Ancient Kings.",
+                       colors::COLOR_PLAYER_DEAD); // TODO color
+    game_state
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::terrain::Terrain;
+
+    #[test]
+    fn levelling_up_spends_experience_and_raises_a_stat() {
+        let mut game_state = new_game(0);
+        let needed = game_state.xp_to_level_up();
+        game_state.elements[PLAYER].xp = needed + 10;
+        assert!(game_state.can_level_up());
+
+        game_state.level_up(StatBoost::MaxHp);
+        let fighter = game_state.elements[PLAYER].fighter.unwrap();
+        assert_eq!(game_state.elements[PLAYER].xp, 10);
+        assert_eq!((fighter.hp, fighter.max_hp), (30 + LEVEL_UP_HP, 30 + LEVEL_UP_HP));
+        assert_eq!(game_state.elements[PLAYER].level, 2);
+        assert_eq!(game_state.xp_to_level_up(), needed + LEVEL_UP_FACTOR);
+        assert!(!game_state.can_level_up());
     }
 
-    log.add("Welcome stranger! Prepare to perish in the Tombs of the Ancient Kings.",
-            colors::COLOR_PLAYER_DEAD); // TODO color
-    GameState {
-        elements: elements,
-        map: map,
-        inventory: inventory,
-        fov_map: fov_map,
-        log: log,
+    #[test]
+    fn elements_without_room_are_left_out() {
+        let mut map = Map::new(4, 3);
+        map.set_terrain(1, 1, Terrain::Floor);
+        map.set_terrain(2, 1, Terrain::Floor);
+        let mut elements = vec![make_player(1, 1)];
+        let left_out = place_near(&map, &mut elements, (1, 1), vec![make_dog(0, 0), make_dog(0, 0)]);
+        assert_eq!(elements[1].pos(), (2, 1));
+        assert_eq!(left_out.len(), 1);
     }
 }
@@ -0,0 +1,217 @@
+//! Lasting effects on elements, such as poison or haste. Each one wears
+//! off after some turns, counted down once per turn whatever the speed of
+//! the element.
+use crate::damage::DamageType;
+use crate::effects;
+use crate::element::{Element, capitalize};
+use crate::messages::{MessageLog, Messages};
+use crate::state::GameState;
+use tcod::colors::{self, Color};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StatusKind {
+    /// takes `potency` poison damage each turn, stacking up
+    Poisoned,
+    /// heals `potency` hit points each turn
+    Regenerating,
+    /// walks in random directions
+    Confused,
+    /// cannot act at all, and cannot be stunned again meanwhile
+    Stunned,
+    /// gains energy twice as fast, cancels Slowed
+    Hasted,
+    /// gains energy half as fast, cancels Hasted
+    Slowed,
+    /// can barely see
+    Blinded,
+    /// can only be noticed from right next to it
+    Invisible,
+}
+
+impl StatusKind {
+    pub fn name(self) -> &'static str {
+        use StatusKind::*;
+        match self {
+            Poisoned => "poisoned",
+            Regenerating => "regenerating",
+            Confused => "confused",
+            Stunned => "stunned",
+            Hasted => "hasted",
+            Slowed => "slowed",
+            Blinded => "blinded",
+            Invisible => "invisible",
+        }
+    }
+
+    pub fn color(self) -> Color {
+        use StatusKind::*;
+        match self {
+            Poisoned => colors::LIME,
+            Regenerating => colors::LIGHT_VIOLET,
+            Confused => colors::LIGHT_BLUE,
+            Stunned => colors::YELLOW,
+            Hasted => colors::LIGHT_CYAN,
+            Slowed => colors::LIGHT_SEPIA,
+            Blinded => colors::GREY,
+            Invisible => colors::LIGHTEST_BLUE,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Status {
+    pub kind: StatusKind,
+    /// turns left before it wears off
+    pub turns: i32,
+    /// strength of effects repeated each turn, such as poison damage
+    pub potency: i32,
+}
+
+pub fn has(element: &Element, kind: StatusKind) -> bool {
+    element.statuses.iter().any(|status| status.kind == kind)
+}
+
+/// Put a status on an element, or add to the one it already has:
+/// poison stacks up and lasts as long as the longest dose, confusion,
+/// blindness and invisibility last longer, stuns do not stack at all,
+/// and others are refreshed. Haste and slowness cancel each other.
+/// Returns false if nothing changed.
+pub fn afflict(element: &mut Element, kind: StatusKind, turns: i32, potency: i32, log: &mut Messages) -> bool {
+    use StatusKind::*;
+    let opposite = match kind {
+        Hasted => Some(Slowed),
+        Slowed => Some(Hasted),
+        _ => None,
+    };
+    if let Some(opposite) = opposite.filter(|&opposite| has(element, opposite)) {
+        element.statuses.retain(|status| status.kind != opposite);
+        log_status(element, opposite, false, log);
+        return true;
+    }
+
+    match element.statuses.iter_mut().find(|status| status.kind == kind) {
+        Some(status) => match kind {
+            Stunned => return false,
+            Poisoned => {
+                status.potency += potency;
+                status.turns = status.turns.max(turns);
+            }
+            Confused | Blinded | Invisible => status.turns += turns,
+            Regenerating | Hasted | Slowed => {
+                status.potency = status.potency.max(potency);
+                status.turns = status.turns.max(turns);
+            }
+        },
+        None => element.statuses.push(Status { kind, turns, potency }),
+    }
+    log_status(element, kind, true, log);
+    true
+}
+
+/// Let a turn pass for the element's statuses: apply their effects, then
+/// remove those wearing off.
+pub fn tick(id: usize, game_state: &mut GameState) {
+    let (x, y) = game_state.elements[id].pos();
+    let mut unseen_log = vec![];
+    let log = if game_state.map.is_in_sight(x, y) { &mut game_state.log } else { &mut unseen_log };
+    let element = &mut game_state.elements[id];
+    let mut xp = None;
+    for status in element.statuses.clone() {
+        match status.kind {
+            StatusKind::Poisoned if element.alive => {
+                xp = effects::damage(element, "poison", status.potency, DamageType::Poison, log);
+            }
+            StatusKind::Regenerating => element.heal(status.potency),
+            _ => {}
+        }
+    }
+    if !element.alive {
+        element.statuses.clear();
+        effects::reward_player(&mut game_state.elements, id, xp, log);
+        return;
+    }
+    for status in element.statuses.iter_mut() {
+        status.turns -= 1;
+    }
+    let worn_off: Vec<_> = element.statuses.iter()
+        .filter(|status| status.turns <= 0)
+        .map(|status| status.kind)
+        .collect();
+    element.statuses.retain(|status| status.turns > 0);
+    for kind in worn_off {
+        log_status(element, kind, false, log);
+    }
+}
+
+/// Whether the player, when the element is in their sight, can make it
+/// out: invisible ones are only seen from right next to them.
+pub fn is_seen_by(element: &Element, player: &Element) -> bool {
+    element.is_player() || !has(element, StatusKind::Invisible) || element.distance_to(player) < 2.0
+}
+
+/// energy the element gains each turn, haste and slowness included
+pub fn speed(element: &Element) -> i32 {
+    if has(element, StatusKind::Hasted) {
+        element.speed * 2
+    } else if has(element, StatusKind::Slowed) {
+        element.speed / 2
+    } else {
+        element.speed
+    }
+}
+
+fn log_status(element: &Element, kind: StatusKind, starts: bool, log: &mut Messages) {
+    let verb = if element.is_player() { "are" } else { "is" };
+    let name = capitalize(&element.name_in_messages());
+    if starts {
+        log.add(format!("{} {} {}!", name, verb, kind.name()), kind.color());
+    } else {
+        log.add(format!("{} {} no longer {}.", name, verb, kind.name()), kind.color());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::element::make_orc;
+
+    fn turns(element: &Element, kind: StatusKind) -> Option<(i32, i32)> {
+        element.statuses.iter()
+            .find(|status| status.kind == kind)
+            .map(|status| (status.turns, status.potency))
+    }
+
+    #[test]
+    fn statuses_stack_their_own_way() {
+        let mut orc = make_orc(0, 0);
+        let mut log = vec![];
+        afflict(&mut orc, StatusKind::Poisoned, 3, 1, &mut log);
+        afflict(&mut orc, StatusKind::Poisoned, 2, 2, &mut log);
+        assert_eq!(turns(&orc, StatusKind::Poisoned), Some((3, 3)));
+
+        afflict(&mut orc, StatusKind::Confused, 3, 0, &mut log);
+        afflict(&mut orc, StatusKind::Confused, 2, 0, &mut log);
+        assert_eq!(turns(&orc, StatusKind::Confused), Some((5, 0)));
+
+        assert!(afflict(&mut orc, StatusKind::Stunned, 2, 0, &mut log));
+        assert!(!afflict(&mut orc, StatusKind::Stunned, 5, 0, &mut log));
+        assert_eq!(turns(&orc, StatusKind::Stunned), Some((2, 0)));
+
+        afflict(&mut orc, StatusKind::Regenerating, 5, 1, &mut log);
+        afflict(&mut orc, StatusKind::Regenerating, 2, 3, &mut log);
+        assert_eq!(turns(&orc, StatusKind::Regenerating), Some((5, 3)));
+    }
+
+    #[test]
+    fn haste_and_slowness_cancel_out() {
+        let mut orc = make_orc(0, 0);
+        let mut log = vec![];
+        afflict(&mut orc, StatusKind::Slowed, 5, 0, &mut log);
+        assert_eq!(speed(&orc), orc.speed / 2);
+        afflict(&mut orc, StatusKind::Hasted, 5, 0, &mut log);
+        assert_eq!(speed(&orc), orc.speed);
+        assert!(orc.statuses.is_empty());
+        let messages: Vec<_> = log.iter().map(|(message, _)| message.as_str()).collect();
+        assert_eq!(messages, ["The orc is slowed!", "The orc is no longer slowed."]);
+    }
+}
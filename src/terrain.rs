@@ -0,0 +1,229 @@
+use crate::colors::*;
+use crate::damage::DamageType;
+use crate::light::Light;
+use tcod::colors::Color;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Terrain {
+    Floor,
+    Wall,
+    Door,
+    OpenDoor,
+    Water,
+    DeepWater,
+    Lava,
+    Rubble,
+    Grass,
+    Stairs,
+    Brazier,
+    GlowingFungus,
+}
+
+/// Everything the game needs to know about a kind of terrain
+#[derive(Debug)]
+pub struct TerrainInfo {
+    pub name: &'static str,
+    pub description: &'static str,
+    /// character drawn on the tile, ' ' for only its background
+    pub glyph: char,
+    pub glyph_color: Color,
+    /// background when the tile is in sight
+    pub lit_color: Color,
+    /// background when the tile was explored but is not in sight
+    pub dark_color: Color,
+    pub walkable: bool,
+    pub transparent: bool,
+    /// number of turns needed to walk on the tile, when walkable
+    pub movement_cost: i32,
+    pub light: Option<Light>,
+    /// damage dealt to whoever stands on the tile, each time they act
+    pub damage: Option<(i32, DamageType)>,
+}
+
+const FLOOR: TerrainInfo = TerrainInfo {
+    name: "floor",
+    description: "Worn flagstones.",
+    glyph: ' ',
+    glyph_color: COLOR_LIGHT_GROUND,
+    lit_color: COLOR_LIGHT_GROUND,
+    dark_color: COLOR_DARK_GROUND,
+    walkable: true,
+    transparent: true,
+    movement_cost: 1,
+    light: None,
+    damage: None,
+};
+
+const WALL: TerrainInfo = TerrainInfo {
+    name: "wall",
+    description: "Rough-hewn stone.",
+    glyph: ' ',
+    glyph_color: COLOR_LIGHT_WALL,
+    lit_color: COLOR_LIGHT_WALL,
+    dark_color: COLOR_DARK_WALL,
+    walkable: false,
+    transparent: false,
+    movement_cost: 0,
+    light: None,
+    damage: None,
+};
+
+const DOOR: TerrainInfo = TerrainInfo {
+    name: "closed door",
+    description: "A heavy wooden door. Walk into it to open it.",
+    glyph: '+',
+    glyph_color: COLOR_DOOR,
+    lit_color: COLOR_LIGHT_WALL,
+    dark_color: COLOR_DARK_WALL,
+    walkable: false,
+    transparent: false,
+    movement_cost: 0,
+    light: None,
+    damage: None,
+};
+
+const OPEN_DOOR: TerrainInfo = TerrainInfo {
+    name: "open door",
+    description: "A heavy wooden door, left open.",
+    glyph: '\'',
+    glyph_color: COLOR_DOOR,
+    lit_color: COLOR_LIGHT_GROUND,
+    dark_color: COLOR_DARK_GROUND,
+    walkable: true,
+    transparent: true,
+    movement_cost: 1,
+    light: None,
+    damage: None,
+};
+
+const WATER: TerrainInfo = TerrainInfo {
+    name: "shallow water",
+    description: "Cold water, up to the knees.",
+    glyph: '~',
+    glyph_color: COLOR_WATER_GLYPH,
+    lit_color: COLOR_LIGHT_WATER,
+    dark_color: COLOR_DARK_WATER,
+    walkable: true,
+    transparent: true,
+    movement_cost: 2,
+    light: None,
+    damage: None,
+};
+
+const DEEP_WATER: TerrainInfo = TerrainInfo {
+    name: "deep water",
+    description: "Dark water. Too deep to wade through.",
+    glyph: '~',
+    glyph_color: COLOR_WATER_GLYPH,
+    lit_color: COLOR_LIGHT_DEEP_WATER,
+    dark_color: COLOR_DARK_DEEP_WATER,
+    walkable: false,
+    transparent: true,
+    movement_cost: 0,
+    light: None,
+    damage: None,
+};
+
+const LAVA: TerrainInfo = TerrainInfo {
+    name: "lava",
+    description: "Molten rock. Walking on it would be a very bad idea.",
+    glyph: '~',
+    glyph_color: COLOR_LAVA_GLYPH,
+    lit_color: COLOR_LIGHT_LAVA,
+    dark_color: COLOR_DARK_LAVA,
+    walkable: true,
+    transparent: true,
+    movement_cost: 1,
+    light: Some(Light { radius: 2, color: COLOR_LAVA_LIGHT }),
+    damage: Some((6, DamageType::Fire)),
+};
+
+const RUBBLE: TerrainInfo = TerrainInfo {
+    name: "rubble",
+    description: "Fallen stones, slow to climb over.",
+    glyph: ',',
+    glyph_color: COLOR_RUBBLE,
+    lit_color: COLOR_LIGHT_GROUND,
+    dark_color: COLOR_DARK_GROUND,
+    walkable: true,
+    transparent: true,
+    movement_cost: 2,
+    light: None,
+    damage: None,
+};
+
+const GRASS: TerrainInfo = TerrainInfo {
+    name: "grass",
+    description: "Pale grass growing without sunlight.",
+    glyph: '"',
+    glyph_color: COLOR_GRASS,
+    lit_color: COLOR_LIGHT_GROUND,
+    dark_color: COLOR_DARK_GROUND,
+    walkable: true,
+    transparent: true,
+    movement_cost: 1,
+    light: None,
+    damage: None,
+};
+
+const STAIRS: TerrainInfo = TerrainInfo {
+    name: "stairs",
+    description: "Stairs leading further down.",
+    glyph: '>',
+    glyph_color: COLOR_STAIRS,
+    lit_color: COLOR_LIGHT_GROUND,
+    dark_color: COLOR_DARK_GROUND,
+    walkable: true,
+    transparent: true,
+    movement_cost: 1,
+    light: None,
+    damage: None,
+};
+
+const BRAZIER: TerrainInfo = TerrainInfo {
+    name: "brazier",
+    description: "A bowl of burning coals, lighting the room around it.",
+    glyph: '*',
+    glyph_color: COLOR_BRAZIER,
+    lit_color: COLOR_LIGHT_GROUND,
+    dark_color: COLOR_DARK_GROUND,
+    walkable: false,
+    transparent: true,
+    movement_cost: 0,
+    light: Some(Light { radius: 7, color: COLOR_BRAZIER_LIGHT }),
+    damage: None,
+};
+
+const GLOWING_FUNGUS: TerrainInfo = TerrainInfo {
+    name: "glowing fungus",
+    description: "Soft mushrooms giving off a pale light.",
+    glyph: ':',
+    glyph_color: COLOR_FUNGUS,
+    lit_color: COLOR_LIGHT_GROUND,
+    dark_color: COLOR_DARK_GROUND,
+    walkable: true,
+    transparent: true,
+    movement_cost: 1,
+    light: Some(Light { radius: 3, color: COLOR_FUNGUS_LIGHT }),
+    damage: None,
+};
+
+impl Terrain {
+    pub fn info(self) -> &'static TerrainInfo {
+        use Terrain::*;
+        match self {
+            Floor => &FLOOR,
+            Wall => &WALL,
+            Door => &DOOR,
+            OpenDoor => &OPEN_DOOR,
+            Water => &WATER,
+            DeepWater => &DEEP_WATER,
+            Lava => &LAVA,
+            Rubble => &RUBBLE,
+            Grass => &GRASS,
+            Stairs => &STAIRS,
+            Brazier => &BRAZIER,
+            GlowingFungus => &GLOWING_FUNGUS,
+        }
+    }
+}
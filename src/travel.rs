@@ -0,0 +1,198 @@
+//! Walking or resting automatically, one turn at a time, until there is
+//! nothing left to do or something worth the player's attention shows up.
+use crate::constants::{PLAYER, TURN_ENERGY};
+use crate::dijkstra::DijkstraMap;
+use crate::faction::Faction;
+use crate::map::{Map, walk_by};
+use crate::messages::{MessageLog, Messages};
+use crate::state::GameState;
+use crate::status;
+use crate::terrain::Terrain;
+use tcod::colors;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AutoMove {
+    /// towards the nearest tile next to an unexplored one
+    Explore,
+    /// towards the nearest known stairs
+    Stairs,
+    /// waiting in place until healed
+    Rest,
+}
+
+/// explored tiles the player could safely walk on, next to unexplored ones
+fn exploration_frontier(map: &Map) -> Vec<(i32, i32)> {
+    let mut frontier = vec![];
+    for x in 0..map.width() {
+        for y in 0..map.height() {
+            let tile = &map[x as usize][y as usize];
+            if !map.is_explored(x, y) || !tile.is_passable() || tile.terrain.info().damage.is_some() {
+                continue;
+            }
+            let next_to_unexplored = (-1..2).any(|dx| (-1..2).any(|dy| {
+                map.is_in_bounds(x + dx, y + dy) && !map.is_explored(x + dx, y + dy)
+            }));
+            if next_to_unexplored {
+                frontier.push((x, y));
+            }
+        }
+    }
+    frontier
+}
+
+fn known_stairs(map: &Map) -> Vec<(i32, i32)> {
+    let mut stairs = vec![];
+    for x in 0..map.width() {
+        for y in 0..map.height() {
+            if map.is_explored(x, y) && map[x as usize][y as usize].terrain == Terrain::Stairs {
+                stairs.push((x, y));
+            }
+        }
+    }
+    stairs
+}
+
+fn stop(log: &mut Messages, auto_move: AutoMove, reason: &str) {
+    let stop = if auto_move == AutoMove::Rest { "You stop resting" } else { "You stop" };
+    log.add(format!("{}: {}.", stop, reason), colors::WHITE);
+}
+
+/// Stop the automatic move, if any, telling the player why.
+pub fn interrupt(game_state: &mut GameState, reason: &str) {
+    if let Some(auto_move) = game_state.auto_move.take() {
+        stop(&mut game_state.log, auto_move, reason);
+    }
+}
+
+/// Take the next step of an automatic move, or the next turn of rest.
+/// Returns false, after telling the player why, when the move is over and
+/// no turn was taken.
+pub fn auto_move_step(game_state: &mut GameState, auto_move: AutoMove) -> bool {
+    let seen_monster = game_state.elements.iter()
+        .find(|element| {
+            element.ai.is_some() &&
+                Faction::Player.is_hostile_to(element.faction) &&
+                game_state.map.is_in_sight(element.position.x, element.position.y) &&
+                status::is_seen_by(element, &game_state.elements[PLAYER])
+        });
+    if let Some(monster) = seen_monster {
+        let reason = format!("the {} is in view", monster.display_name);
+        stop(&mut game_state.log, auto_move, &reason);
+        return false;
+    }
+
+    match auto_move {
+        AutoMove::Explore => {
+            let frontier = exploration_frontier(&game_state.map);
+            walk_towards(game_state, &frontier, "There is nothing left to explore here.")
+        }
+        AutoMove::Stairs => {
+            let (x, y) = game_state.elements[PLAYER].pos();
+            if game_state.map[x as usize][y as usize].terrain == Terrain::Stairs {
+                game_state.log.add("You reach the stairs.", colors::WHITE);
+                return false;
+            }
+            let stairs = known_stairs(&game_state.map);
+            walk_towards(game_state, &stairs, "You do not know of any stairs you could reach.")
+        }
+        AutoMove::Rest => rest_turn(game_state),
+    }
+}
+
+/// Take a step towards the nearest of the goals, or tell the player there
+/// is none to walk to.
+fn walk_towards(game_state: &mut GameState, goals: &[(i32, i32)], nothing_left: &str) -> bool {
+    let map = &game_state.map;
+    let mut dijkstra_map = DijkstraMap::new(map.width(), map.height(), true);
+    dijkstra_map.compute(map, goals);
+    let (x, y) = game_state.elements[PLAYER].pos();
+    match dijkstra_map.next_step(PLAYER, map, &game_state.elements) {
+        Some((next_x, next_y)) => {
+            walk_by(PLAYER, &mut game_state.map, &mut game_state.elements, next_x - x, next_y - y);
+            true
+        }
+        None => {
+            game_state.log.add(nothing_left, colors::WHITE);
+            false
+        }
+    }
+}
+
+/// Wait a turn, unless the player is already at full health. Getting hurt
+/// stops the rest too, see `interrupt`.
+fn rest_turn(game_state: &mut GameState) -> bool {
+    let player = &mut game_state.elements[PLAYER];
+    if player.fighter.is_none_or(|f| f.hp >= f.max_hp) {
+        stop(&mut game_state.log, AutoMove::Rest, "you are at full health");
+        return false;
+    }
+    player.energy -= TURN_ENERGY;
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::element::{make_orc, make_player};
+    use crate::scheduler::pass_time;
+    use crate::utils::seeded_rng;
+
+    /// a hurt player alone in an open room
+    fn hurt_player() -> GameState {
+        let mut map = Map::new(10, 6);
+        for x in 1..9 {
+            for y in 1..5 {
+                map.set_terrain(x, y, Terrain::Floor);
+            }
+        }
+        let mut player = make_player(1, 1);
+        player.fighter.as_mut().unwrap().hp -= 2;
+        let mut game_state = GameState::new(map, vec![player], seeded_rng(0));
+        game_state.update_fov();
+        game_state
+    }
+
+    /// rest the way the player does, until something stops it
+    fn rest(game_state: &mut GameState) {
+        game_state.auto_move = Some(AutoMove::Rest);
+        while game_state.auto_move.is_some() {
+            if !auto_move_step(game_state, AutoMove::Rest) {
+                game_state.auto_move = None;
+                return;
+            }
+            pass_time(game_state);
+        }
+    }
+
+    fn last_message(game_state: &GameState) -> Option<&str> {
+        game_state.log.last().map(|(message, _)| message.as_str())
+    }
+
+    #[test]
+    fn resting_heals_the_player_fully() {
+        let mut game_state = hurt_player();
+        rest(&mut game_state);
+        let fighter = game_state.elements[PLAYER].fighter.unwrap();
+        assert_eq!(fighter.hp, fighter.max_hp);
+        assert_eq!(last_message(&game_state), Some("You stop resting: you are at full health."));
+    }
+
+    #[test]
+    fn resting_stops_when_a_monster_shows_up() {
+        let mut game_state = hurt_player();
+        game_state.elements.push(make_orc(4, 3));
+        game_state.update_fov();
+        rest(&mut game_state);
+        assert_eq!(last_message(&game_state), Some("You stop resting: the orc is in view."));
+        assert_eq!(game_state.elements[PLAYER].energy, TURN_ENERGY);
+    }
+
+    #[test]
+    fn resting_stops_when_hurt() {
+        let mut game_state = hurt_player();
+        game_state.map.set_terrain(1, 1, Terrain::Lava);
+        rest(&mut game_state);
+        assert_eq!(last_message(&game_state), Some("You stop resting: you are hurt."));
+        assert!(game_state.elements[PLAYER].alive);
+    }
+}
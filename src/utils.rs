@@ -1,3 +1,4 @@
+use rand::{SeedableRng, StdRng};
 use std::cmp;
 
 /// Mutably borrow two *separate* elements from the given slice.
@@ -12,3 +13,11 @@ pub fn mut_two<T>(first_index: usize, second_index: usize, items: &mut [T]) -> (
         (&mut second_slice[0], &mut first_slice[second_index])
     }
 }
+
+/// The random number generator driving a whole game.
+/// Always built from a seed so a given dungeon can be generated again.
+pub type GameRng = StdRng;
+
+pub fn seeded_rng(seed: usize) -> GameRng {
+    StdRng::from_seed(&[seed])
+}
@@ -0,0 +1,203 @@
+//! Parsing and transformation of hand-designed rooms ("vaults").
+//! The format is described at the top of `vaults.txt`.
+
+use crate::terrain::Terrain;
+
+const VAULTS_SOURCE: &str = include_str!("../vaults.txt");
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum VaultCell {
+    /// keep whatever the generator put there
+    Untouched,
+    Wall,
+    Terrain(Terrain),
+    Orc,
+    Troll,
+    Potion,
+    /// a random monster or item, chosen when the vault is stamped
+    RandomSpawn,
+}
+
+impl VaultCell {
+    fn from_char(c: char) -> Option<Self> {
+        match c {
+            ' ' => Some(VaultCell::Untouched),
+            '#' => Some(VaultCell::Wall),
+            '.' => Some(VaultCell::Terrain(Terrain::Floor)),
+            '+' => Some(VaultCell::Terrain(Terrain::Door)),
+            '~' => Some(VaultCell::Terrain(Terrain::Water)),
+            '=' => Some(VaultCell::Terrain(Terrain::DeepWater)),
+            '^' => Some(VaultCell::Terrain(Terrain::Lava)),
+            ',' => Some(VaultCell::Terrain(Terrain::Rubble)),
+            '"' => Some(VaultCell::Terrain(Terrain::Grass)),
+            '*' => Some(VaultCell::Terrain(Terrain::Brazier)),
+            ':' => Some(VaultCell::Terrain(Terrain::GlowingFungus)),
+            'o' => Some(VaultCell::Orc),
+            'T' => Some(VaultCell::Troll),
+            '!' => Some(VaultCell::Potion),
+            '?' => Some(VaultCell::RandomSpawn),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Vault {
+    pub name: String,
+    /// cells[y][x]
+    cells: Vec<Vec<VaultCell>>,
+}
+
+impl Vault {
+    pub fn width(&self) -> i32 {
+        self.cells.first().map_or(0, |row| row.len() as i32)
+    }
+
+    pub fn height(&self) -> i32 {
+        self.cells.len() as i32
+    }
+
+    pub fn get(&self, x: i32, y: i32) -> VaultCell {
+        self.cells[y as usize][x as usize]
+    }
+
+    /// Whether corridors must go around the cell: they only dig through the
+    /// walls on the middle row and column, to reach the centre.
+    pub fn blocks_corridors(&self, x: i32, y: i32) -> bool {
+        let (middle_x, middle_y) = ((self.width() - 1) / 2, (self.height() - 1) / 2);
+        self.get(x, y) == VaultCell::Wall && x != middle_x && y != middle_y
+    }
+
+    /// the same vault turned a quarter clockwise
+    pub fn rotated(&self) -> Vault {
+        let (width, height) = (self.width() as usize, self.height() as usize);
+        let cells = (0..width).map(|x| {
+            (0..height).map(|y| self.cells[height - 1 - y][x]).collect()
+        }).collect();
+        Vault { name: self.name.clone(), cells }
+    }
+
+    /// the same vault flipped from left to right
+    pub fn mirrored(&self) -> Vault {
+        let cells = self.cells.iter().map(|row| {
+            row.iter().rev().cloned().collect()
+        }).collect();
+        Vault { name: self.name.clone(), cells }
+    }
+}
+
+/// parse every vault defined in `vaults.txt`
+pub fn load_vaults() -> Result<Vec<Vault>, String> {
+    parse_vaults(VAULTS_SOURCE)
+}
+
+pub fn parse_vaults(source: &str) -> Result<Vec<Vault>, String> {
+    let mut vaults = vec![];
+    let mut current: Option<(String, Vec<&str>)> = None;
+
+    // an empty line always ends the vault being read
+    for (line_number, line) in source.lines().chain(std::iter::once("")).enumerate() {
+        if line.trim().is_empty() {
+            if let Some((name, rows)) = current.take() {
+                vaults.push(build_vault(name, &rows)?);
+            }
+        } else if let Some(ref mut vault) = current {
+            vault.1.push(line);
+        } else if line.starts_with('#') {
+            // comment between two vaults
+        } else if let Some(name) = line.strip_prefix("vault ") {
+            current = Some((name.trim().to_owned(), vec![]));
+        } else {
+            return Err(format!("line {}: expected a \"vault <name>\" line", line_number + 1));
+        }
+    }
+    Ok(vaults)
+}
+
+fn build_vault(name: String, rows: &[&str]) -> Result<Vault, String> {
+    if rows.is_empty() {
+        return Err(format!("vault {} has no layout", name));
+    }
+    // lines may omit their trailing spaces
+    let width = rows.iter().map(|row| row.chars().count()).max().unwrap_or(0);
+    let mut cells = vec![];
+    for row in rows {
+        let mut cell_row = vec![];
+        for c in row.chars() {
+            match VaultCell::from_char(c) {
+                Some(cell) => cell_row.push(cell),
+                None => return Err(format!("vault {}: unknown character '{}'", name, c)),
+            }
+        }
+        cell_row.resize(width, VaultCell::Untouched);
+        cells.push(cell_row);
+    }
+    Ok(Vault { name, cells })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ARROW: &str = "\
+# a comment
+vault arrow
+#.
+##=
+";
+
+    fn rows(vault: &Vault) -> Vec<Vec<VaultCell>> {
+        (0..vault.height())
+            .map(|y| (0..vault.width()).map(|x| vault.get(x, y)).collect())
+            .collect()
+    }
+
+    #[test]
+    fn vaults_are_parsed_and_padded() {
+        let vaults = parse_vaults(ARROW).unwrap();
+        assert_eq!(vaults.len(), 1);
+        let vault = &vaults[0];
+        assert_eq!(vault.name, "arrow");
+        assert_eq!((vault.width(), vault.height()), (3, 2));
+        assert_eq!(vault.get(1, 0), VaultCell::Terrain(Terrain::Floor));
+        assert_eq!(vault.get(2, 0), VaultCell::Untouched);
+        assert_eq!(vault.get(2, 1), VaultCell::Terrain(Terrain::DeepWater));
+    }
+
+    #[test]
+    fn vaults_end_at_empty_lines() {
+        let vaults = parse_vaults("vault a\n#\n\nvault b\n.\n").unwrap();
+        let names: Vec<_> = vaults.iter().map(|vault| vault.name.as_str()).collect();
+        assert_eq!(names, ["a", "b"]);
+    }
+
+    #[test]
+    fn invalid_vaults_are_reported() {
+        assert_eq!(parse_vaults(".#.").unwrap_err(), "line 1: expected a \"vault <name>\" line");
+        assert_eq!(parse_vaults("vault empty\n").unwrap_err(), "vault empty has no layout");
+        assert_eq!(parse_vaults("vault odd\n#x#").unwrap_err(), "vault odd: unknown character 'x'");
+    }
+
+    #[test]
+    fn vaults_are_rotated_and_mirrored() {
+        use VaultCell::{Untouched, Wall};
+        let floor = VaultCell::Terrain(Terrain::Floor);
+        let deep = VaultCell::Terrain(Terrain::DeepWater);
+        let vault = parse_vaults(ARROW).unwrap().remove(0);
+
+        assert_eq!(rows(&vault.rotated()), [[Wall, Wall], [Wall, floor], [deep, Untouched]]);
+        assert_eq!(rows(&vault.mirrored()), [[Untouched, floor, Wall], [deep, Wall, Wall]]);
+        let turned_around = vault.rotated().rotated().rotated().rotated();
+        assert_eq!(rows(&turned_around), rows(&vault));
+    }
+
+    #[test]
+    fn only_walls_off_the_middle_block_corridors() {
+        let vault = parse_vaults("vault box\n###\n#.#\n###").unwrap().remove(0);
+        assert!(vault.blocks_corridors(0, 0));
+        assert!(vault.blocks_corridors(2, 2));
+        assert!(!vault.blocks_corridors(1, 0));
+        assert!(!vault.blocks_corridors(0, 1));
+        assert!(!vault.blocks_corridors(1, 1));
+    }
+}